@@ -0,0 +1,377 @@
+use std::{cell::RefCell, rc::Rc};
+
+use chrono::{DateTime, Duration, Local, Utc};
+use gloo::timers::callback::{Interval, Timeout};
+use serde::{Serialize, Deserialize};
+use web_sys::{AudioContext, HtmlAudioElement, HtmlInputElement, HtmlSelectElement, NotificationPermission, OscillatorType};
+use yew::prelude::*;
+
+use crate::{clock, notifications, storage::{self, Schema, SaveError, StorableData, StorageBackend}};
+
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum AlarmTone {
+    // The original timer_expired.wav.
+    Classic,
+    Beep,
+    Chime,
+    Bell,
+}
+
+impl AlarmTone {
+    pub const ALL: [AlarmTone; 4] = [AlarmTone::Classic, AlarmTone::Beep, AlarmTone::Chime, AlarmTone::Bell];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlarmTone::Classic => "Classic",
+            AlarmTone::Beep => "Beep",
+            AlarmTone::Chime => "Chime",
+            AlarmTone::Bell => "Bell",
+        }
+    }
+
+    // Notes as (frequency in Hz, start, length) in seconds from when the tone is played.
+    fn notes(&self) -> &'static [(f32, f64, f64)] {
+        match self {
+            AlarmTone::Classic => &[],
+            AlarmTone::Beep => &[(880.0, 0.0, 0.15), (880.0, 0.25, 0.15), (880.0, 0.5, 0.15)],
+            AlarmTone::Chime => &[(660.0, 0.0, 0.4), (880.0, 0.3, 0.4), (1320.0, 0.6, 0.8)],
+            AlarmTone::Bell => &[(523.25, 0.0, 1.8), (1046.5, 0.0, 1.2)],
+        }
+    }
+
+    fn wave(&self) -> OscillatorType {
+        match self {
+            AlarmTone::Beep => OscillatorType::Square,
+            _ => OscillatorType::Sine,
+        }
+    }
+
+    pub fn play(&self, volume: f32) {
+        if let AlarmTone::Classic = self {
+            if let Ok(sound) = HtmlAudioElement::new_with_src("timer_expired.wav") {
+                sound.set_volume(volume.into());
+                let _ = sound.play();
+            }
+            return;
+        }
+        AUDIO.with(|audio| {
+            let mut audio = audio.borrow_mut();
+            if audio.is_none() {
+                *audio = AudioContext::new().ok();
+            }
+            let context = match audio.as_ref() {
+                Some(context) => context,
+                None => return log::error!("Web Audio is not available, the {} alarm can't play.", self.name()),
+            };
+            // Browsers start the context suspended until the page has been interacted with.
+            let _ = context.resume();
+            let now = context.current_time();
+            for (frequency, start, length) in self.notes() {
+                if let Err(err) = self.play_note(context, now + start, *frequency, *length, volume) {
+                    log::error!("Could not play alarm tone: {:?}", err);
+                }
+            }
+        });
+    }
+
+    fn play_note(&self, context: &AudioContext, at: f64, frequency: f32, length: f64, volume: f32) -> Result<(), wasm_bindgen::JsValue> {
+        let oscillator = context.create_oscillator()?;
+        oscillator.set_type(self.wave());
+        oscillator.frequency().set_value(frequency);
+        let gain = context.create_gain()?;
+        // Fading out avoids the click of cutting the wave off mid cycle.
+        gain.gain().set_value_at_time(volume.max(0.0001), at)?;
+        gain.gain().exponential_ramp_to_value_at_time(0.0001, at + length)?;
+        oscillator.connect_with_audio_node(&gain)?;
+        gain.connect_with_audio_node(&context.destination())?;
+        oscillator.start_with_when(at)?;
+        oscillator.stop_with_when(at + length)
+    }
+}
+
+thread_local! {
+    static AUDIO: RefCell<Option<AudioContext>> = const { RefCell::new(None) };
+}
+
+// Each kind of timer has its own alarm settings, so kitchen timers can sound different from the CS cycle.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AlarmKind {
+    CsCycle,
+    KitchenTimer,
+}
+
+impl AlarmKind {
+    pub fn key(&self) -> &'static str {
+        match self {
+            AlarmKind::CsCycle => "cs_cycle",
+            AlarmKind::KitchenTimer => "kitchen_timer",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct AlarmSettings {
+    pub tone: AlarmTone,
+    pub volume: f32, // 0 to 1
+    // How often the tone plays again until the alarm is acknowledged.
+    pub repeat_seconds: u32,
+    pub snooze_minutes: u32,
+    #[serde(skip)]
+    pub kind: Option<AlarmKind>,
+}
+
+impl Default for AlarmSettings {
+    fn default() -> Self {
+        AlarmSettings { tone: AlarmTone::Classic, volume: 1.0, repeat_seconds: 10, snooze_minutes: 2, kind: None }
+    }
+}
+
+pub const ALARM_SETTINGS: Schema<AlarmSettings> = Schema::new("alarm_settings", "Alarm settings", &[]);
+
+impl StorableData for AlarmSettings {
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self {
+        ALARM_SETTINGS.load(backend).unwrap_or_default()
+    }
+
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        match self.kind {
+            Some(kind) => ALARM_SETTINGS.save_to(backend, &ALARM_SETTINGS.instance_key(kind.key()), self),
+            None => ALARM_SETTINGS.save(backend, self),
+        }
+    }
+}
+
+impl AlarmSettings {
+    pub fn load_for_kind<B: StorageBackend + ?Sized>(backend: &B, kind: AlarmKind) -> Self {
+        let mut settings = ALARM_SETTINGS.load_from(backend, &ALARM_SETTINGS.instance_key(kind.key())).unwrap_or_default();
+        settings.kind = Some(kind);
+        settings
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AlarmStatus {
+    Silent,
+    Ringing,
+    Snoozed(DateTime<Utc>),
+}
+
+// Settings and the running repeat or snooze timer. Shared so timer callbacks created on the first
+// render still see settings changed later.
+struct AlarmInner {
+    settings: AlarmSettings,
+    repeat: Option<Interval>,
+    snooze: Option<Timeout>,
+}
+
+#[derive(Clone)]
+pub struct AlarmHandle {
+    inner: Rc<RefCell<AlarmInner>>,
+    status: UseStateHandle<AlarmStatus>,
+    update: UseForceUpdateHandle,
+}
+
+impl PartialEq for AlarmHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner) && *self.status == *other.status
+    }
+}
+
+impl AlarmHandle {
+    pub fn status(&self) -> AlarmStatus {
+        *self.status
+    }
+
+    pub fn settings(&self) -> AlarmSettings {
+        self.inner.borrow().settings.clone()
+    }
+
+    pub fn set_settings(&self, settings: AlarmSettings) {
+        settings.persist();
+        self.inner.borrow_mut().settings = settings;
+        self.update.force_update();
+    }
+
+    // Plays the tone now and again every repeat_seconds until acknowledged or snoozed.
+    pub fn ring(&self) {
+        let mut inner = self.inner.borrow_mut();
+        if inner.repeat.is_some() {
+            return;
+        }
+        inner.snooze = None;
+        // Another card may have changed the settings for this kind since they were loaded.
+        if let Some(kind) = inner.settings.kind {
+            inner.settings = AlarmSettings::load_for_kind(&*storage::backend(), kind);
+        }
+        let settings = inner.settings.clone();
+        settings.tone.play(settings.volume);
+        let handle = self.clone();
+        inner.repeat = Some(Interval::new(settings.repeat_seconds.max(1) * 1000, move || {
+            let settings = handle.settings();
+            settings.tone.play(settings.volume);
+        }));
+        self.status.set(AlarmStatus::Ringing);
+    }
+
+    pub fn acknowledge(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.repeat = None;
+        inner.snooze = None;
+        self.status.set(AlarmStatus::Silent);
+    }
+
+    // Silences the alarm and rings again after snooze_minutes.
+    pub fn snooze(&self) {
+        let mut inner = self.inner.borrow_mut();
+        let minutes = inner.settings.snooze_minutes.max(1);
+        inner.repeat = None;
+        let handle = self.clone();
+        inner.snooze = Some(Timeout::new(minutes * 60 * 1000, move || handle.ring()));
+        self.status.set(AlarmStatus::Snoozed(clock::now() + Duration::minutes(minutes.into())));
+    }
+}
+
+#[hook]
+pub fn use_alarm(kind: AlarmKind) -> AlarmHandle {
+    let inner = use_mut_ref(|| AlarmInner {
+        settings: AlarmSettings::load_for_kind(&*storage::backend(), kind),
+        repeat: None,
+        snooze: None,
+    });
+    let status = use_state_eq(|| AlarmStatus::Silent);
+    let update = use_force_update();
+    {
+        let inner = inner.clone();
+        use_effect_with_deps(move |_| {
+            move || {
+                let mut inner = inner.borrow_mut();
+                inner.repeat = None;
+                inner.snooze = None;
+            }
+        }, ());
+    }
+    AlarmHandle { inner, status, update }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct AlarmData {
+    pub alarm: AlarmHandle,
+    #[prop_or("Time's up".to_string())]
+    pub message: String,
+}
+
+// Shown while an alarm is ringing or snoozed, with the buttons to silence it.
+#[function_component]
+pub fn AlarmBanner(data: &AlarmData) -> Html {
+    let acknowledge = {
+        let alarm = data.alarm.clone();
+        Callback::from(move |_| alarm.acknowledge())
+    };
+    let snooze = {
+        let alarm = data.alarm.clone();
+        Callback::from(move |_| alarm.snooze())
+    };
+    let snooze_minutes = data.alarm.settings().snooze_minutes.max(1);
+    html! {
+        if let AlarmStatus::Ringing | AlarmStatus::Snoozed(_) = data.alarm.status() {
+            <div class="alarm_banner">
+                <span class="material-symbols-outlined icon">{ "alarm" }</span>
+                <p>{
+                    match data.alarm.status() {
+                        AlarmStatus::Snoozed(until) => format!("Snoozed until {}", until.with_timezone(&Local).format("%-I:%M %p")),
+                        _ => data.message.clone(),
+                    }
+                }</p>
+                <button class="button" onclick={acknowledge}>{ "Acknowledge" }</button>
+                if data.alarm.status() == AlarmStatus::Ringing {
+                    { " " }
+                    <button class="button outlined" onclick={snooze}>{ format!("Snooze {} min", snooze_minutes) }</button>
+                }
+            </div>
+        }
+    }
+}
+
+#[function_component]
+pub fn AlarmSettingsEditor(data: &AlarmData) -> Html {
+    let settings = data.alarm.settings();
+    let change = |apply: fn(&mut AlarmSettings, &str)| {
+        let alarm = data.alarm.clone();
+        Callback::from(move |e: Event| {
+            let value = match e.target_dyn_into::<HtmlSelectElement>() {
+                Some(select) => select.value(),
+                None => e.target_unchecked_into::<HtmlInputElement>().value(),
+            };
+            let mut settings = alarm.settings();
+            apply(&mut settings, &value);
+            alarm.set_settings(settings);
+        })
+    };
+    let tone_changed = change(|settings, value| {
+        if let Some(tone) = AlarmTone::ALL.iter().find(|tone| tone.name() == value) {
+            settings.tone = *tone;
+        }
+    });
+    let volume_changed = change(|settings, value| {
+        if let Ok(percent) = value.parse::<f32>() {
+            settings.volume = (percent / 100.0).clamp(0.0, 1.0);
+        }
+    });
+    let repeat_changed = change(|settings, value| {
+        if let Ok(seconds) = value.parse::<u32>() {
+            settings.repeat_seconds = seconds.max(1);
+        }
+    });
+    let snooze_changed = change(|settings, value| {
+        if let Ok(minutes) = value.parse::<u32>() {
+            settings.snooze_minutes = minutes.max(1);
+        }
+    });
+    let test = {
+        let settings = settings.clone();
+        Callback::from(move |_| settings.tone.play(settings.volume))
+    };
+    let permission = use_state(notifications::permission);
+    let allow_notifications = {
+        let permission = permission.clone();
+        Callback::from(move |_| {
+            let permission = permission.clone();
+            notifications::request_permission(Callback::from(move |answer| permission.set(Some(answer))));
+        })
+    };
+    html! {
+        <div class="alarm_settings">
+            <label>{ "Sound " }
+                <select onchange={tone_changed}>
+                {
+                    for AlarmTone::ALL.iter().map(|tone| html! {
+                        <option value={ tone.name() } selected={ *tone == settings.tone }>{ tone.name() }</option>
+                    })
+                }
+                </select>
+            </label>
+            <button class="button outlined" onclick={test}><span class="material-symbols-outlined">{ "volume_up" }</span>{ " Test" }</button>
+            <label>{ "Volume " }
+                <input type="range" min="0" max="100" value={ ((settings.volume * 100.0).round() as u32).to_string() } onchange={volume_changed} />
+            </label>
+            <label>{ "Repeat every " }
+                <input class="number_input" type="number" min="1" value={ settings.repeat_seconds.to_string() } onchange={repeat_changed} />{ " seconds" }
+            </label>
+            <label>{ "Snooze for " }
+                <input class="number_input" type="number" min="1" value={ settings.snooze_minutes.to_string() } onchange={snooze_changed} />{ " minutes" }
+            </label>
+            <p class="alarm_notifications">{ "System notifications: " }
+            {
+                match *permission {
+                    None => html! { "not supported by this browser" },
+                    Some(NotificationPermission::Granted) => html! { "on" },
+                    Some(NotificationPermission::Denied) => html! { "blocked, allow them for this site in the browser settings" },
+                    Some(_) => html! {
+                        <button class="button outlined" onclick={allow_notifications}><span class="material-symbols-outlined">{ "notifications_active" }</span>{ " Allow" }</button>
+                    },
+                }
+            }
+            </p>
+        </div>
+    }
+}
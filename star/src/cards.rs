@@ -0,0 +1,220 @@
+use yew::prelude::*;
+
+use crate::{components::*, timers::KitchenTimersCard};
+
+// Everything Star needs to know about a kind of card. To add a card, implement this in its own
+// module and list it in REGISTRY, the launcher and Card pick it up from there.
+pub trait CardKind {
+    // Stored with every card of this kind, so it must never change once cards have been saved.
+    fn key(&self) -> &'static str;
+    // Names the card in labels and menus.
+    fn name(&self) -> &'static str;
+    // Shown at the top of the card, empty for cards that bring their own heading.
+    fn title(&self) -> &'static str {
+        self.name()
+    }
+    fn icon(&self) -> &'static str;
+    // Text for the launcher button, cards without one can't be started from the launcher.
+    fn launcher_label(&self) -> Option<&'static str> {
+        None
+    }
+    fn content(&self, card: &CardData) -> Html;
+    // Schema key the card's own state is stored under, one value per card.
+    fn persistence_key(&self) -> Option<&'static str> {
+        None
+    }
+    // Cards with per-card state can be added more than once, e.g. a CS cycle per brewer.
+    fn allows_multiple(&self) -> bool {
+        false
+    }
+    fn closable(&self) -> bool {
+        true
+    }
+
+    fn card_type(&self) -> CardType {
+        CardType::new(self.key())
+    }
+}
+
+pub const REGISTRY: &[&dyn CardKind] = &[
+    &Launcher,
+    &CsCycleCard,
+    &DaydotsCard,
+    &TasksCard,
+    &KitchenTimersCard,
+    &BackupCard,
+];
+
+pub fn kind(card_type: &CardType) -> Option<&'static dyn CardKind> {
+    REGISTRY.iter().copied().find(|kind| kind.key() == card_type.key())
+}
+
+pub struct Launcher;
+
+impl Launcher {
+    pub const KEY: &'static str = "StartNewTask";
+}
+
+impl CardKind for Launcher {
+    fn key(&self) -> &'static str {
+        Self::KEY
+    }
+
+    fn name(&self) -> &'static str {
+        "New task"
+    }
+
+    fn title(&self) -> &'static str {
+        "What can I help you with today?"
+    }
+
+    fn icon(&self) -> &'static str {
+        "add"
+    }
+
+    fn content(&self, card: &CardData) -> Html {
+        html! {
+            <div class="card-multioption">
+            {
+                for REGISTRY.iter().filter_map(|kind| kind.launcher_label().map(|label| (kind, label))).map(|(kind, label)| {
+                    let create_card = {
+                        let card_type = kind.card_type();
+                        let add_priority_card = card.add_priority_card.clone();
+                        Callback::from(move |_| {
+                            add_priority_card.emit(card_type.clone());
+                        })
+                    };
+                    html! {
+                        <a class="card-multioption_button" onclick={ create_card }>
+                        <span class="icon material-symbols-outlined">{ kind.icon() }</span>
+                        { label }
+                        </a>
+                    }
+                })
+            }
+            </div>
+        }
+    }
+
+    fn closable(&self) -> bool {
+        false
+    }
+}
+
+pub struct CsCycleCard;
+
+impl CardKind for CsCycleCard {
+    fn key(&self) -> &'static str {
+        "CsCycle"
+    }
+
+    fn name(&self) -> &'static str {
+        "CS Cycle"
+    }
+
+    fn title(&self) -> &'static str {
+        ""
+    }
+
+    fn icon(&self) -> &'static str {
+        "update"
+    }
+
+    fn launcher_label(&self) -> Option<&'static str> {
+        Some("Start a new CS cycle")
+    }
+
+    fn content(&self, card: &CardData) -> Html {
+        html! { <CsCycleCardContent card={card.id} label={card.label.clone()} /> }
+    }
+
+    fn persistence_key(&self) -> Option<&'static str> {
+        Some(CS_CYCLE.key)
+    }
+
+    fn allows_multiple(&self) -> bool {
+        true
+    }
+}
+
+pub struct DaydotsCard;
+
+impl CardKind for DaydotsCard {
+    fn key(&self) -> &'static str {
+        "Daydots"
+    }
+
+    fn name(&self) -> &'static str {
+        "Daydots"
+    }
+
+    fn title(&self) -> &'static str {
+        ""
+    }
+
+    fn icon(&self) -> &'static str {
+        "event"
+    }
+
+    fn launcher_label(&self) -> Option<&'static str> {
+        Some("Daydot some backups")
+    }
+
+    fn content(&self, _card: &CardData) -> Html {
+        html! {
+            <Daydots />
+        }
+    }
+}
+
+pub struct TasksCard;
+
+impl CardKind for TasksCard {
+    fn key(&self) -> &'static str {
+        "Tasks"
+    }
+
+    fn name(&self) -> &'static str {
+        "Tasks"
+    }
+
+    fn icon(&self) -> &'static str {
+        "checklist"
+    }
+
+    fn launcher_label(&self) -> Option<&'static str> {
+        Some("View my tasks for today")
+    }
+
+    fn content(&self, _card: &CardData) -> Html {
+        html! {
+            <DailyTasks />
+        }
+    }
+}
+
+pub struct BackupCard;
+
+impl CardKind for BackupCard {
+    fn key(&self) -> &'static str {
+        "Backup"
+    }
+
+    fn name(&self) -> &'static str {
+        "Backup"
+    }
+
+    fn icon(&self) -> &'static str {
+        "save"
+    }
+
+    fn launcher_label(&self) -> Option<&'static str> {
+        Some("Back up or restore my data")
+    }
+
+    fn content(&self, _card: &CardData) -> Html {
+        html! {
+            <Backup />
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use web_sys::{HtmlInputElement, KeyboardEvent};
+use yew::prelude::*;
+
+use crate::{editor::{self, ListChange}, storage::{Schema, SaveError, StorableData, StorageBackend}};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ChecklistItem {
+    pub name: String,
+    // Already done by the time a cycle is started, like the coffee brewed to kick it off.
+    #[serde(default)]
+    pub ticked_at_start: bool,
+    // Ticking it pours the cycle's brew into the urns.
+    #[serde(default)]
+    pub brews: bool,
+}
+
+const BREW_STEP: &str = "Brew Coffee";
+
+impl ChecklistItem {
+    fn new(name: &str, ticked_at_start: bool) -> Self {
+        ChecklistItem { name: name.to_string(), ticked_at_start, brews: false }
+    }
+
+    fn brew_step() -> Self {
+        ChecklistItem { brews: true, ..Self::new(BREW_STEP, true) }
+    }
+}
+
+// The tasks worked through during a CS cycle, shared by every CS cycle card.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct CsChecklist {
+    pub cycle: Vec<ChecklistItem>,
+    pub flex: Vec<ChecklistItem>,
+}
+
+impl Default for CsChecklist {
+    fn default() -> Self {
+        CsChecklist {
+            cycle: vec![
+                ChecklistItem::brew_step(),
+                ChecklistItem::new("Cafe Check", false),
+                ChecklistItem::new("Restock", false),
+                ChecklistItem::new("Cycle Task", false),
+            ],
+            flex: vec![
+                ChecklistItem::brew_step(),
+                ChecklistItem::new("Cafe Check", false),
+            ],
+        }
+    }
+}
+
+pub const CS_CHECKLIST: Schema<CsChecklist> = Schema::new("cs_checklist", "CS checklist", &[checklist_brew_step]);
+
+// Version 0 -> 1: the step that fills the urns is flagged instead of being found by its name.
+fn checklist_brew_step(mut checklist: Value) -> Result<Value, String> {
+    for list in ["cycle", "flex"] {
+        for item in checklist.get_mut(list).and_then(Value::as_array_mut).ok_or("checklist is missing its lists")? {
+            let item = item.as_object_mut().ok_or("checklist item is not an object")?;
+            let brews = item.get("name").and_then(Value::as_str) == Some(BREW_STEP);
+            item.insert("brews".to_string(), Value::Bool(brews));
+        }
+    }
+    Ok(checklist)
+}
+
+impl StorableData for CsChecklist {
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self {
+        CS_CHECKLIST.load(backend).unwrap_or_default()
+    }
+
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        CS_CHECKLIST.save(backend, self)
+    }
+}
+
+impl CsChecklist {
+    pub fn items(&self, flex: bool) -> &[ChecklistItem] {
+        if flex { &self.flex } else { &self.cycle }
+    }
+
+    fn items_mut(&mut self, flex: bool) -> &mut Vec<ChecklistItem> {
+        if flex { &mut self.flex } else { &mut self.cycle }
+    }
+
+    // What a new cycle starts out with ticked.
+    pub fn ticked_at_start(&self, flex: bool) -> Vec<String> {
+        self.items(flex).iter().filter(|item| item.ticked_at_start).map(|item| item.name.clone()).collect()
+    }
+
+    // Whether a new cycle starts out with its brew already in the urns.
+    pub fn brews_at_start(&self, flex: bool) -> bool {
+        self.items(flex).iter().any(|item| item.brews && item.ticked_at_start)
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ChecklistEditorData {
+    pub checklist: CsChecklist,
+    pub on_change: Callback<CsChecklist>,
+}
+
+#[function_component]
+pub fn ChecklistEditor(data: &ChecklistEditorData) -> Html {
+    let editing_flex = use_state(|| false);
+    let name_input_ref = use_node_ref();
+    let name_error = use_state(|| None::<String>);
+    let flex = *editing_flex;
+    let items = data.checklist.items(flex);
+
+    let edit = |change: ListChange<ChecklistItem>| {
+        editor::save_edit(&data.checklist, &data.on_change, move |checklist: &mut CsChecklist| change(checklist.items_mut(flex)))
+    };
+
+    let add_item = {
+        let checklist = data.checklist.clone();
+        let on_change = data.on_change.clone();
+        let name_input_ref = name_input_ref.clone();
+        let name_error = name_error.clone();
+        Callback::from(move |_| {
+            let input = name_input_ref.cast::<HtmlInputElement>().expect("Checklist input not initialized correctly.");
+            let name = input.value().trim().to_string();
+            if name.is_empty() {
+                return name_error.set(Some("Give the task a name.".to_string()));
+            }
+            // Ticked tasks are stored by name, so two with the same name would tick together.
+            if checklist.items(flex).iter().any(|item| item.name == name) {
+                return name_error.set(Some(format!("{} is already on the list.", name)));
+            }
+            let mut checklist = checklist.clone();
+            checklist.items_mut(flex).push(ChecklistItem { name, ticked_at_start: false, brews: false });
+            checklist.persist();
+            input.set_value("");
+            name_error.set(None);
+            on_change.emit(checklist);
+        })
+    };
+
+    let add_on_enter = {
+        let add_item = add_item.clone();
+        Callback::from(move |e: KeyboardEvent| {
+            if e.key() == "Enter" {
+                add_item.emit(());
+            }
+        })
+    };
+
+    let select_list = |flex: bool| {
+        let editing_flex = editing_flex.clone();
+        let name_error = name_error.clone();
+        Callback::from(move |_| {
+            editing_flex.set(flex);
+            name_error.set(None);
+        })
+    };
+
+    html! {
+        <div class="checklist_editor">
+            <p>
+                <a class={ classes!("clickable", (!flex).then_some("selected")) } onclick={ select_list(false) }>{ "Cycle tasks" }</a>
+                { " | " }
+                <a class={ classes!("clickable", flex.then_some("selected")) } onclick={ select_list(true) }>{ "Flex tasks" }</a>
+            </p>
+            {
+                for items.iter().enumerate().map(|(index, item)| {
+                    html! {
+                        <div class="checklist_item" key={ item.name.clone() }>
+                            <span class="checklist_item_name">{ item.name.clone() }</span>
+                            <a class="clickable" title="Ticked when a cycle starts" onclick={ edit(Box::new(move |items| items[index].ticked_at_start = !items[index].ticked_at_start)) }>
+                                <span class="material-symbols-outlined">{ if item.ticked_at_start { "check_circle" } else { "radio_button_unchecked" } }</span>
+                            </a>
+                            <a class={ classes!("clickable", (!item.brews).then_some("checklist_flag_off")) } title="Fills the urns when ticked" onclick={ edit(Box::new(move |items| items[index].brews = !items[index].brews)) }>
+                                <span class="material-symbols-outlined">{ "coffee_maker" }</span>
+                            </a>
+                            { editor::row_buttons(index, items.len(), edit) }
+                        </div>
+                    }
+                })
+            }
+            if items.is_empty() {
+                <p>{ "No tasks yet." }</p>
+            }
+            <input ref={ name_input_ref } onkeydown={ add_on_enter } class="text_input" size="1" type="text" placeholder="New task, e.g. Wipe down condiment bar" />
+            if let Some(error) = &*name_error {
+                <p class="input_error">{ error }</p>
+            }
+            <button class="button" onclick={ add_item.reform(|_| ()) }><span class="material-symbols-outlined icon">{ "add" }</span>{ " Add Task" }</button>
+            <button class="button outlined" onclick={ edit(Box::new(move |items| *items = CsChecklist::default().items(flex).to_vec())) }>{ "Reset to default" }</button>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_checklists_flag_the_brew_step() {
+        let stored = r#"{"version":0,"data":{"cycle":[{"name":"Brew Coffee","ticked_at_start":true},{"name":"Restock","ticked_at_start":false}],"flex":[{"name":"Cafe Check"}]}}"#;
+        let checklist = CS_CHECKLIST.upgrade(stored).unwrap();
+        assert_eq!(checklist.cycle.iter().map(|item| item.brews).collect::<Vec<_>>(), vec![true, false]);
+        assert!(checklist.brews_at_start(false));
+        assert!(!checklist.brews_at_start(true));
+    }
+}
@@ -0,0 +1,99 @@
+use std::{cell::{Cell, RefCell}, rc::Rc};
+
+use chrono::{Date, DateTime, Local, Utc};
+#[cfg(test)]
+use chrono::Duration;
+
+// Where the app gets the current time. Everything time dependent goes through clock() instead of
+// calling chrono directly, so a FakeClock can stand in for it.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+
+    fn local_now(&self) -> DateTime<Local> {
+        self.now().with_timezone(&Local)
+    }
+
+    fn today(&self) -> Date<Local> {
+        self.local_now().date()
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+// A clock that only moves when told to, for tests. Clones share the same time.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct FakeClock(Rc<Cell<DateTime<Utc>>>);
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(at: DateTime<Utc>) -> Self {
+        FakeClock(Rc::new(Cell::new(at)))
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.0.set(self.0.get() + by);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0.get()
+    }
+}
+
+thread_local! {
+    static CLOCK: RefCell<Rc<dyn Clock>> = RefCell::new(Rc::new(SystemClock));
+}
+
+pub fn clock() -> Rc<dyn Clock> {
+    CLOCK.with(|clock| clock.borrow().clone())
+}
+
+// The app itself always runs on the system clock.
+#[cfg(test)]
+pub fn set_clock(clock: Rc<dyn Clock>) {
+    CLOCK.with(|current| *current.borrow_mut() = clock);
+}
+
+pub fn now() -> DateTime<Utc> {
+    clock().now()
+}
+
+pub fn local_now() -> DateTime<Local> {
+    clock().local_now()
+}
+
+pub fn today() -> Date<Local> {
+    clock().today()
+}
+
+thread_local! {
+    static LAST_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+// Millisecond timestamp with room for a counter, so ids stay unique across reloads and within a millisecond.
+// Used for cards and everything else staff add, like kitchen timers, urns and roasts. Being timestamps, they stay clear of the legacy roast ids.
+pub fn unique_id() -> u64 {
+    LAST_ID.with(|last| {
+        let id = (now().timestamp_millis() as u64 * 1000).max(last.get() + 1);
+        last.set(id);
+        id
+    })
+}
+
+// Puts the thread on a FakeClock set to the given local time, each test runs on its own thread.
+#[cfg(test)]
+pub fn fake_local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> FakeClock {
+    use chrono::TimeZone;
+    let clock = FakeClock::new(Local.ymd(year, month, day).and_hms(hour, minute, 0).with_timezone(&Utc));
+    set_clock(Rc::new(clock.clone()));
+    clock
+}
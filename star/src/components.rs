@@ -1,26 +1,24 @@
-use std::{cell::Cell};
+use std::{borrow::Cow, rc::Rc};
 
-use chrono::{Date, Local, Datelike, Weekday, Duration, Timelike};
-use gloo::storage::{LocalStorage, Storage};
+use chrono::{DateTime, Local, Utc, Datelike, Timelike, Weekday, Duration};
 use serde::{Serialize, Deserialize};
+use serde_json::Value;
 
-use web_sys::{HtmlAudioElement, HtmlInputElement, window};
+use wasm_bindgen::JsCast;
+use gloo::timers::callback::{Interval, Timeout};
+use web_sys::{Element, HtmlElement, HtmlInputElement, KeyboardEvent, PointerEvent, window};
 use yew::{prelude::*};
 
 
 
-use crate::{state::{ TimerData, TimerAction, Tasks }, storage::StorableData};
+use crate::{alarm::{use_alarm, AlarmBanner, AlarmKind, AlarmSettingsEditor}, cards::{self, CardKind}, checklist::{ChecklistEditor, CsChecklist}, clock, files::use_files, history::{CycleLog, CycleRecord, CycleStats}, notifications, products::{DaydotProduct, ProductCatalog, ProductCatalogEditor, Station}, roasts::{self, RoastCatalog, RoastCatalogEditor, RoastId}, rotation::{RotationEditor, RotationRules}, urns::{UrnTracker, Urns}, state::{ TimerData, TimerAction, TimerInterval, TimerEvent, Tasks, Lane, DropTarget }, storage::{self, StorableData, StorageBackend, Schema, SaveError, Bundle, ImportMode, Change}};
 
 #[function_component]
 pub fn Timer(data:&TimerData) -> Html {
-    log::warn!("Render");
-    if data.time_left.get() < 0 && data.timer_sound.paused() {
-        data.timer_sound.play();
-    }
     html! {
         <>
-        <h2 class={ "timer_label".to_owned() + (if data.time_left.get() < 0 { " timer_expired"} else { "" })}>{ 
-            TimerData::format_time_left(data.time_left.get()) 
+        <h2 class={ "timer_label".to_owned() + (if *data.time_left < 0 { " timer_expired"} else { "" })}>{ 
+            TimerData::format_time_left(*data.time_left) 
         }</h2>
         </>
     }
@@ -34,106 +32,265 @@ pub enum CsState {
     Flex
 }
 
-#[derive(PartialEq, Copy, Clone, Serialize, Deserialize, Debug)]
-pub enum CoffeeRoast {
-    Pike,
-    Blonde,
-    Dark
-}
-
-impl CoffeeRoast {
-    pub fn to_string(&self) -> String {
-        match self {
-            CoffeeRoast::Blonde => {
-                String::from("Blonde")
-            },
-            CoffeeRoast::Dark => {
-                String::from("Dark")
-            }
-            CoffeeRoast::Pike => {
-                String::from("Pike")
-            },
-        }
-    }
-}
-
-#[derive(Copy, Clone, PartialEq, Serialize, Deserialize, Debug)]
-pub struct CoffeesToBrew(CoffeeRoast, Option<CoffeeRoast>); // (Last Brewed, Optional: Next to brew)
+// The roasts brewed together at the start of a cycle, worked out by the brew rotation rules.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct CoffeesToBrew(pub Vec<RoastId>);
 
 impl CoffeesToBrew {
-    pub fn to_string(&self) -> String {
-        let mut val = self.0.to_string() + ", ";
-        if let Some(roast) = &self.1 {
-            val.push_str(&roast.to_string());
-        }
-        val
+    pub fn names(&self, catalog: &RoastCatalog) -> Vec<String> {
+        self.0.iter().map(|roast| catalog.name(*roast)).collect()
     }
 
-    pub fn get_next(&self) -> CoffeesToBrew {
-        let mut c = CoffeesToBrew(CoffeeRoast::Pike, None);
-        if chrono::Local::now().hour() < 11 {
-            if let Some(roast) = &self.1 {
-                match roast {
-                    CoffeeRoast::Blonde => {
-                        c.1 = Some(CoffeeRoast::Dark);
-                    },
-                    _ => {
-                        c.1 = Some(CoffeeRoast::Blonde);
-                    }
-                }
-            }
+    pub fn tags(&self, catalog: &RoastCatalog) -> Html {
+        html! {
+            for self.0.iter().map(|roast| catalog.tag(*roast))
         }
-        c
     }
 }
 
 #[derive(Properties, Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct CsData {
     pub cycle_state:CsState,
-    pub current_cycle_time:i32,
+    // When the running cycle's timer runs out, None while no cycle is running.
+    #[serde(default)]
+    pub deadline: Option<DateTime<Utc>>,
+    // Set while the cycle is paused, resuming moves the deadline back by the time since.
+    #[serde(default)]
+    #[prop_or_default]
+    pub paused_at: Option<DateTime<Utc>>,
+    // Time the cycle spent paused, so it isn't counted as time spent on the cycle.
+    #[serde(default)]
+    #[prop_or_default]
+    pub paused_seconds: i32,
+    // When the running cycle was started, for the history log.
+    #[serde(default)]
+    #[prop_or_default]
+    pub started_at: Option<DateTime<Utc>>,
+    // Names of the checklist items ticked off so far this cycle.
+    #[serde(default)]
+    #[prop_or_default]
+    pub tasks_done: Vec<String>,
+    // Set once the cycle's brew has gone into the urns, so ticking the brew step again doesn't refill them.
+    #[serde(default)]
+    #[prop_or_default]
+    pub urns_filled: bool,
     pub start_time_value: i32,
+    // Flex cycles run during slow periods, with a longer interval and fewer tasks.
+    #[serde(default)]
+    #[prop_or_default]
+    pub flex_mode: bool,
+    #[serde(default = "default_flex_time")]
+    #[prop_or(DEFAULT_FLEX_TIME)]
+    pub flex_time_value: i32,
     pub last_brewed: CoffeesToBrew,
+    // The card this cycle belongs to, each CS cycle card keeps its own state.
+    #[serde(skip)]
+    #[prop_or_default]
+    pub card: Option<CardId>,
+    // The card's label, so alerts can say which cycle is due.
+    #[serde(skip)]
+    #[prop_or_default]
+    pub label: Option<String>,
+}
+
+const DEFAULT_FLEX_TIME: i32 = 3600;
+
+fn default_flex_time() -> i32 {
+    DEFAULT_FLEX_TIME
+}
+
+pub const CS_CYCLE: Schema<CsData> = Schema::new("cs_cycle", "CS cycle", &[storage::wrap_unversioned, cs_cycle_deadline, cs_cycle_brew_list, cs_cycle_roast_ids]);
+
+// Version 1 -> 2: a running cycle is stored as the time it runs out instead of the seconds it had left.
+// When those seconds were saved is unknown, so the cycle carries on from them as of now.
+fn cs_cycle_deadline(mut data: Value) -> Result<Value, String> {
+    let cycle = data.as_object_mut().ok_or("CS cycle is not an object")?;
+    let seconds_left = cycle.remove("current_cycle_time").and_then(|seconds| seconds.as_i64());
+    let running = cycle.get("cycle_state").and_then(Value::as_str) == Some("Started");
+    let deadline = match seconds_left {
+        Some(seconds) if running => serde_json::to_value(clock::now() + Duration::seconds(seconds)).map_err(|e| e.to_string())?,
+        _ => Value::Null,
+    };
+    cycle.insert("deadline".to_string(), deadline);
+    Ok(data)
+}
+
+// Version 2 -> 3: the last brew is a list of roasts instead of a roast and an optional second one.
+fn cs_cycle_brew_list(mut data: Value) -> Result<Value, String> {
+    let cycle = data.as_object_mut().ok_or("CS cycle is not an object")?;
+    let brewed = match cycle.remove("last_brewed") {
+        Some(Value::Array(roasts)) => roasts.into_iter().filter(|roast| !roast.is_null()).collect(),
+        _ => Vec::new(),
+    };
+    cycle.insert("last_brewed".to_string(), Value::Array(brewed));
+    Ok(data)
+}
+
+// Version 3 -> 4: roasts are catalog ids instead of names.
+fn cs_cycle_roast_ids(mut data: Value) -> Result<Value, String> {
+    let cycle = data.as_object_mut().ok_or("CS cycle is not an object")?;
+    let brewed = cycle.remove("last_brewed").unwrap_or_default();
+    cycle.insert("last_brewed".to_string(), roasts::migrate_roast_names(brewed));
+    Ok(data)
 }
 
 impl StorableData for CsData {
-    fn load() -> CsData {
-        if let Ok(stored_data) = LocalStorage::get("cs_cycle") { log::debug!("{:?}", stored_data); stored_data } else { 
-            CsData {
-                cycle_state: CsState::NotStarted,
-                current_cycle_time: 1800,
-                start_time_value: 1800,
-                last_brewed: CoffeesToBrew(CoffeeRoast::Pike, None),
-            }
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> CsData {
+        CS_CYCLE.load(backend).unwrap_or(CsData {
+            cycle_state: CsState::NotStarted,
+            deadline: None,
+            paused_at: None,
+            paused_seconds: 0,
+            started_at: None,
+            tasks_done: Vec::new(),
+            urns_filled: false,
+            start_time_value: 1800,
+            flex_mode: false,
+            flex_time_value: DEFAULT_FLEX_TIME,
+            last_brewed: CoffeesToBrew(vec![roasts::PIKE]),
+            card: None,
+            label: None,
+        })
+    }
+
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        match self.card {
+            Some(card) => CS_CYCLE.save_to(backend, &CS_CYCLE.instance_key(card), self),
+            None => CS_CYCLE.save(backend, self),
         }
     }
+}
 
-    fn save(self) -> Self {
-        let data = self.clone();
-        LocalStorage::set("cs_cycle", data);
-        self
+impl CsData {
+    pub fn load_for_card<B: StorageBackend + ?Sized>(backend: &B, card: CardId) -> CsData {
+        let key = CS_CYCLE.instance_key(card);
+        let mut data = match CS_CYCLE.load_from(backend, &key) {
+            Some(data) => data,
+            None => {
+                // The first card to load after cycles became per-card takes over the old shared cycle.
+                let data = Self::load(backend);
+                if backend.get(CS_CYCLE.key).is_some() && CS_CYCLE.save_to(backend, &key, &data).is_ok() {
+                    backend.remove(CS_CYCLE.key);
+                }
+                data
+            }
+        };
+        data.card = Some(card);
+        data
     }
 }
 
+#[derive(Properties, PartialEq)]
+pub struct CsCycleCardData {
+    pub card: CardId,
+    pub label: Option<String>,
+}
+
+// Loads the card's cycle on its first render only, as loading can take over the old shared cycle.
+#[function_component]
+pub fn CsCycleCardContent(props: &CsCycleCardData) -> Html {
+    let loaded = use_memo(|card| CsData::load_for_card(&*storage::backend(), *card), props.card);
+    let data = CsData { label: props.label.clone(), ..(*loaded).clone() };
+    html! { <CsCycle ..data /> }
+}
+
+// The brew step pours what the cycle brewed into the urns, restarting their hold times.
+fn fill_urns(brew: &CoffeesToBrew) {
+    let mut urns = Urns::load(&*storage::backend());
+    urns.fill(brew, clock::now());
+    urns.persist();
+}
+
 #[function_component]
 pub fn CsCycle(data:&CsData) -> Html {
     let state = use_state(|| {   
         data.cycle_state
     });
 
-    //CoffeesToBrew(CoffeeRoast::Pike, 
-    //    if chrono::Local::now().hour() < 11 { Some(CoffeeRoast::Blonde) } else { None }
-    let last_brewed = use_state(|| data.last_brewed);
+    let last_brewed = use_state(|| data.last_brewed.clone());
+    let rotation = use_state(|| RotationRules::load(&*storage::backend()));
+    let catalog = use_state(|| RoastCatalog::load(&*storage::backend()));
 
     //Timer initialization
     let start_time_value = use_state(|| data.start_time_value);
-    let start_time_input_str = use_state(|| TimerData::format_time_left(*start_time_value));
+    let flex_mode = use_state(|| data.flex_mode);
+    let flex_time_value = use_state(|| data.flex_time_value);
+    // The duration the input edits and the next cycle runs for, depending on the mode.
+    let duration_value = if *flex_mode { flex_time_value.clone() } else { start_time_value.clone() };
+    let start_time_input_str = use_state(|| TimerData::format_time_left(*duration_value));
     let start_time_input_ref = use_node_ref();
+    let start_time_error = use_state(|| None::<String>);
+    let time_left = use_state(|| data.deadline.map_or(*duration_value, |deadline| TimerData::seconds_between(data.paused_at.unwrap_or_else(clock::now), deadline)));
+    let started_at = use_state(|| data.started_at);
+    let tasks_done = use_state(|| data.tasks_done.clone());
+    let urns_filled = use_state(|| data.urns_filled);
+    let checklist = use_state(|| CsChecklist::load(&*storage::backend()));
+    let alarm = use_alarm(AlarmKind::CsCycle);
+    let show_alarm_settings = use_state(|| false);
+    let show_history = use_state(|| false);
+    let show_checklist_editor = use_state(|| false);
+    let show_rotation_editor = use_state(|| false);
+    let show_catalog_editor = use_state(|| false);
+    let on_timer_event = {
+        let alarm = alarm.clone();
+        let card = data.card;
+        let tag = card.map_or_else(|| "star-cs-cycle".to_string(), |card| format!("star-card-{}", card));
+        let name = data.label.clone().unwrap_or_else(|| "The CS cycle".to_string());
+        Callback::from(move |event:TimerEvent| {
+            if !matches!(event, TimerEvent::Tick(_)) {
+                log::info!("CS cycle timer: {:?}", event);
+            }
+            match event {
+                TimerEvent::Expired => {
+                    alarm.ring();
+                    notifications::post("CS cycle due", &format!("{} has run out.", name), &tag, card);
+                },
+                TimerEvent::Stopped => alarm.acknowledge(),
+                _ => (),
+            }
+        })
+    };
+    let toggle_alarm_settings = {
+        let show_alarm_settings = show_alarm_settings.clone();
+        Callback::from(move |_| show_alarm_settings.set(!*show_alarm_settings))
+    };
+    let toggle_history = {
+        let show_history = show_history.clone();
+        Callback::from(move |_| show_history.set(!*show_history))
+    };
+    let toggle_checklist_editor = {
+        let show_checklist_editor = show_checklist_editor.clone();
+        Callback::from(move |_| show_checklist_editor.set(!*show_checklist_editor))
+    };
+    let toggle_rotation_editor = {
+        let show_rotation_editor = show_rotation_editor.clone();
+        Callback::from(move |_| show_rotation_editor.set(!*show_rotation_editor))
+    };
+    let rotation_changed = {
+        let rotation = rotation.clone();
+        Callback::from(move |edited: RotationRules| rotation.set(edited))
+    };
+    let toggle_catalog_editor = {
+        let show_catalog_editor = show_catalog_editor.clone();
+        Callback::from(move |_| show_catalog_editor.set(!*show_catalog_editor))
+    };
+    let catalog_changed = {
+        let catalog = catalog.clone();
+        Callback::from(move |edited: RoastCatalog| catalog.set(edited))
+    };
+    let checklist_changed = {
+        let checklist = checklist.clone();
+        Callback::from(move |edited: CsChecklist| checklist.set(edited))
+    };
     let timer_data = TimerData {
-        time_left:use_state(|| Cell::new(data.current_cycle_time)),
-        running: data.cycle_state == CsState::Started,
-        callback:Callback::noop(),
-        timer_interval_id: -1,
-        timer_sound: HtmlAudioElement::new_with_src("timer_expired.wav").expect("Could not load timer sound."),
+        deadline: data.deadline,
+        time_left: time_left.clone(),
+        paused_at: data.paused_at,
+        paused_seconds: data.paused_seconds,
+        running: data.deadline.is_some() && data.paused_at.is_none(),
+        callback: on_timer_event,
+        warning_seconds: 60,
+        interval: TimerInterval::default(),
     };
     let timer_state = use_reducer(|| timer_data);
 
@@ -141,146 +298,277 @@ pub fn CsCycle(data:&CsData) -> Html {
     let start_cycle = {
         let state = state.clone();
         let timer_state = timer_state.clone();
-        let start_time_value = start_time_value.clone();
+        let duration_value = duration_value.clone();
+        let flex_mode = *flex_mode;
         let last_brewed = last_brewed.clone();
+        let started_at = started_at.clone();
+        let tasks_done = tasks_done.clone();
+        let urns_filled = urns_filled.clone();
+        let checklist = checklist.clone();
         Callback::from(move |_| {
-            timer_state.dispatch(TimerAction::Start(*start_time_value));
-            last_brewed.set((*last_brewed).get_next());
-            state.set(CsState::Started)
+            started_at.set(Some(clock::now()));
+            // Picked up fresh in case another card changed the checklist since this one loaded it.
+            let current = CsChecklist::load(&*storage::backend());
+            let ticked = current.ticked_at_start(flex_mode);
+            timer_state.dispatch(TimerAction::Start(TimerData::deadline_in(*duration_value)));
+            // Worked out again as of the click, the rules may give something else than when the card was drawn.
+            let rotation = RotationRules::load(&*storage::backend());
+            let catalog = RoastCatalog::load(&*storage::backend());
+            let brew = rotation.next_brew(&catalog, &last_brewed, clock::local_now());
+            let brewed = current.brews_at_start(flex_mode);
+            if brewed {
+                fill_urns(&brew);
+            }
+            urns_filled.set(brewed);
+            tasks_done.set(ticked);
+            checklist.set(current);
+            last_brewed.set(brew);
+            state.set(if flex_mode { CsState::Flex } else { CsState::Started })
         })
     };
     
     let stop_cycle = {
         let state = state.clone();
         let timer_state = timer_state.clone();
-        let start_time_value = start_time_value.clone();
+        let duration_value = duration_value.clone();
+        let started_at = started_at.clone();
+        let tasks_done = tasks_done.clone();
+        let urns_filled = urns_filled.clone();
+        let last_brewed = last_brewed.clone();
+        let label = data.label.clone();
         Callback::from(move |_| {
+            let seconds_left = timer_state.seconds_left().unwrap_or_default();
+            CycleLog::record(CycleRecord {
+                // Cycles from before the log existed didn't store their start, so it's worked back from the deadline.
+                started_at: (*started_at).unwrap_or_else(|| clock::now() - Duration::seconds((*duration_value - seconds_left + timer_state.total_paused_seconds()).into())),
+                ended_at: clock::now(),
+                cycle: label.clone(),
+                flex: *state == CsState::Flex,
+                duration: *duration_value,
+                paused_seconds: timer_state.total_paused_seconds(),
+                overrun_seconds: (-seconds_left).max(0),
+                brewed: last_brewed.names(&RoastCatalog::load(&*storage::backend())),
+                tasks_done: (*tasks_done).clone(),
+            });
+            started_at.set(None);
+            tasks_done.set(Vec::new());
+            urns_filled.set(false);
             timer_state.dispatch(TimerAction::Stop);
-            (*timer_state.time_left).set(*start_time_value);
+            timer_state.time_left.set(*duration_value);
             state.set(CsState::NotStarted);
         })
     };
 
-    let start_time_changed = {
-        let _timer_state = timer_state.clone();
-        let start_time_input_ref = start_time_input_ref.clone();
-        let start_time_value = start_time_value.clone();
+    let toggle_flex_mode = {
+        let flex_mode = flex_mode.clone();
+        let duration = if *flex_mode { *start_time_value } else { *flex_time_value };
         let start_time_input_str = start_time_input_str.clone();
+        let start_time_error = start_time_error.clone();
+        let time_left = time_left.clone();
         Callback::from(move |_| {
-            let input = start_time_input_ref.cast::<HtmlInputElement>().expect("Timer not initialized correctly.");
-            let mut formatted_input_value:String = String::new();
-            for c in input.value().chars() {
-                if !c.is_numeric() && c != ':' {
-                    continue;
-                }
-                formatted_input_value.push(c);
-            }
-            if let Ok(start_time) = TimerData::seconds_from_str(formatted_input_value) {
-                start_time_value.set(start_time.into());
-                start_time_input_str.set(TimerData::format_time_left(start_time.into()));
-            }
-            else {
-                start_time_input_str.set(String::new());
-            }
+            flex_mode.set(!*flex_mode);
+            start_time_input_str.set(TimerData::format_time_left(duration));
+            start_time_error.set(None);
+            time_left.set(duration);
         })
     };
-    let next_to_brew = (*last_brewed).get_next();
-    let save_data = {
+
+    let pause_cycle = {
         let timer_state = timer_state.clone();
-        let state = state.clone();
-        let last_brewed = last_brewed.clone();
-        let start_time = start_time_value.clone();
+        Callback::from(move |_| timer_state.dispatch(TimerAction::Pause))
+    };
+
+    let resume_cycle = {
+        let timer_state = timer_state.clone();
+        Callback::from(move |_| timer_state.dispatch(TimerAction::Resume))
+    };
+
+    let start_time_changed = {
+        let start_time_input_ref = start_time_input_ref.clone();
+        let start_time_value = duration_value.clone();
+        let start_time_input_str = start_time_input_str.clone();
+        let start_time_error = start_time_error.clone();
         Callback::from(move |_| {
-            log::info!("{:?}", *state);
-            let mut d = CsData {
-                current_cycle_time: (*timer_state.time_left).get(),
-                cycle_state: CsState::NotStarted,
-                last_brewed: (*last_brewed),
-                start_time_value: *start_time,
-            };
-            if d.start_time_value != d.current_cycle_time {
-                d.cycle_state = CsState::Started;
+            let input = start_time_input_ref.cast::<HtmlInputElement>().expect("Timer not initialized correctly.");
+            match TimerData::seconds_from_str(&input.value()) {
+                Ok(start_time) if start_time > 0 => {
+                    start_time_value.set(start_time);
+                    start_time_input_str.set(TimerData::format_time_left(start_time));
+                    start_time_error.set(None);
+                },
+                result => {
+                    // Keep what was typed so it can be corrected rather than retyped.
+                    start_time_input_str.set(input.value());
+                    start_time_error.set(Some(match result {
+                        Err(err) => err.to_string(),
+                        Ok(_) => "A cycle needs to be longer than 0 seconds.".to_string(),
+                    }));
+                },
             }
-            log::error!("{:?}", d);
-            d.save();
         })
     };
-    // Initialize save data callback
+    let next_to_brew = rotation.next_brew(&catalog, &last_brewed, clock::local_now());
+    // Resume a cycle that was running when the card was last open, the deadline says how far along it is now.
+    // Closing the card only stops the countdown, the deadline is kept for when it's back.
     {
         let timer_state = timer_state.clone();
-        let started = data.cycle_state == CsState::Started;
-        let save_data = save_data;
-        let destructor_save = save_data.clone();
+        let running = data.deadline.is_some() && data.paused_at.is_none();
         use_effect_with_deps(move |_| {
-            if started {
-                println!("Closure");
-                timer_state.dispatch(TimerAction::Start(timer_state.time_left.get()));
-            }
-            let listener = gloo::events::EventListener::new(&window().unwrap(), "beforeunload", move |_| save_data.emit(()));
-            move || {
-                drop(listener);
-                destructor_save.emit(());
+            if running {
+                timer_state.dispatch(TimerAction::Resume);
             }
+            move || timer_state.stop()
         }, ());
     }
+    // Save whenever the cycle changes. The deadline doesn't move while the timer runs, so there is nothing to save per tick.
+    {
+        let card = data.card;
+        use_effect_with_deps(move |(cycle_state, deadline, paused_at, paused_seconds, last_brewed, (start_time_value, flex_mode, flex_time_value, started_at, tasks_done, urns_filled))| {
+            CsData {
+                cycle_state: *cycle_state,
+                deadline: *deadline,
+                paused_at: *paused_at,
+                paused_seconds: *paused_seconds,
+                started_at: *started_at,
+                tasks_done: tasks_done.clone(),
+                urns_filled: *urns_filled,
+                last_brewed: last_brewed.clone(),
+                start_time_value: *start_time_value,
+                flex_mode: *flex_mode,
+                flex_time_value: *flex_time_value,
+                card,
+                label: None,
+            }.persist();
+            || ()
+        }, (*state, timer_state.deadline, timer_state.paused_at, timer_state.paused_seconds, (*last_brewed).clone(), (*start_time_value, *flex_mode, *flex_time_value, *started_at, (*tasks_done).clone(), *urns_filled)));
+    }
     html! {
         <>
             <div class="timer" >
             if *state == CsState::NotStarted {
                 <input ref={ start_time_input_ref } onchange={ start_time_changed } value={ (*start_time_input_str).clone() } class="timer_input" size="1" type="text" />
-                <button class="button" onclick={start_cycle}><span class="material-symbols-outlined icon">{ "update" }</span>{ " Start Cycle" }</button>
+                if let Some(error) = &*start_time_error {
+                    <p class="input_error">{ error }</p>
+                }
+                <button class="button" onclick={start_cycle} disabled={ start_time_error.is_some() }><span class="material-symbols-outlined icon">{ "update" }</span>{ if *flex_mode { " Start Flex Cycle" } else { " Start Cycle" } }</button>
+                { " " }
+                <button class="button outlined" onclick={toggle_flex_mode} title="Flex cycles are for slow periods, with a longer interval and fewer tasks.">
+                    <span class="material-symbols-outlined">{ if *flex_mode { "toggle_on" } else { "toggle_off" } }</span>{ " Flex" }
+                </button>
             }
             else {
-                <Timer time_left={ timer_state.time_left.clone() } timer_sound={ timer_state.timer_sound.clone() } />
+                <Timer time_left={ time_left.clone() } />
+                <p class="timer_breakdown">
+                    { format!("Active {}, paused {}", TimerData::format_time_left(*duration_value - *time_left), TimerData::format_time_left(timer_state.total_paused_seconds())) }
+                    if let Some(paused_at) = timer_state.paused_at {
+                        { format!(". Paused since {}", paused_at.with_timezone(&Local).format("%-I:%M %p")) }
+                    }
+                </p>
+                if timer_state.paused_at.is_some() {
+                    <button class="button" onclick={resume_cycle}><span class="material-symbols-outlined icon">{ "play_arrow" }</span>{ " Resume" }</button>
+                } else {
+                    <button class="button" onclick={pause_cycle}><span class="material-symbols-outlined icon">{ "pause" }</span>{ " Pause" }</button>
+                }
+                { " " }
                 <button class="button outlined" onclick={stop_cycle}>{ "Stop Cycle" }</button>
+                <AlarmBanner alarm={ alarm.clone() } message="The CS cycle is due" />
             }
             </div>
+            <a class="settings_toggle clickable" onclick={toggle_alarm_settings}>
+                <span class="material-symbols-outlined">{ "notifications" }</span>{ " Alarm settings" }
+            </a>
+            { " " }
+            <a class="settings_toggle clickable" onclick={toggle_history}>
+                <span class="material-symbols-outlined">{ "query_stats" }</span>{ " History" }
+            </a>
+            { " " }
+            <a class="settings_toggle clickable" onclick={toggle_checklist_editor}>
+                <span class="material-symbols-outlined">{ "edit_note" }</span>{ " Checklist" }
+            </a>
+            { " " }
+            <a class="settings_toggle clickable" onclick={toggle_rotation_editor}>
+                <span class="material-symbols-outlined">{ "coffee" }</span>{ " Brew rotation" }
+            </a>
+            { " " }
+            <a class="settings_toggle clickable" onclick={toggle_catalog_editor}>
+                <span class="material-symbols-outlined">{ "palette" }</span>{ " Roasts" }
+            </a>
+            if *show_alarm_settings {
+                <AlarmSettingsEditor alarm={ alarm.clone() } />
+            }
+            if *show_checklist_editor {
+                <ChecklistEditor checklist={ (*checklist).clone() } on_change={ checklist_changed } />
+            }
+            if *show_rotation_editor {
+                <RotationEditor rules={ (*rotation).clone() } catalog={ (*catalog).clone() } on_change={ rotation_changed } />
+            }
+            if *show_catalog_editor {
+                <RoastCatalogEditor catalog={ (*catalog).clone() } on_change={ catalog_changed } />
+            }
+            if *show_history {
+                <CycleStats />
+            }
             <hr/>
             if *state == CsState::NotStarted {
-                <p>{ "The CS cycle begins with brewing coffee. Brew some coffee and click " }<b>{ "Start Cycle" }</b>{ " to begin." }</p>
+                if *flex_mode {
+                    <p>{ "Flex cycles are for slow periods. They run longer and only cover the essentials. Brew some coffee and click " }<b>{ "Start Flex Cycle" }</b>{ " to begin." }</p>
+                } else {
+                    <p>{ "The CS cycle begins with brewing coffee. Brew some coffee and click " }<b>{ "Start Cycle" }</b>{ " to begin." }</p>
+                }
                 <hr/>
-                if next_to_brew.1 != None {
-                    <p><b>{ "Next coffee to brew:" }</b><br/>{
-                        next_to_brew.0.to_string() +
-                        &(if let Some(p) = next_to_brew.1 {
-                            " & ".to_owned() + &p.to_string()
-                        } else {
-                            "".to_owned()
-                        })
-                        }
-                    </p>
+                if !next_to_brew.0.is_empty() {
+                    <p><b>{ "Next coffee to brew:" }</b><br/>{ next_to_brew.tags(&catalog) }</p>
                 }
             } else {
-                <p><b>{ "Tasks" }</b></p>
-                <Checkbox text="Brew Coffee" initial_value={true} />
-                <Checkbox text="Cafe Check"/>
-                <Checkbox text="Restock"/>
-                <Checkbox text="Cycle Task"/>
+                <p><b>{ if *state == CsState::Flex { "Flex Tasks" } else { "Tasks" } }</b></p>
+                {
+                    for checklist.items(*state == CsState::Flex).iter().map(|task| {
+                        let task_ticked = {
+                            let tasks_done = tasks_done.clone();
+                            let urns_filled = urns_filled.clone();
+                            let last_brewed = last_brewed.clone();
+                            let brews = task.brews;
+                            let task = task.name.clone();
+                            Callback::from(move |done: bool| {
+                                if done && brews && !*urns_filled {
+                                    fill_urns(&last_brewed);
+                                    urns_filled.set(true);
+                                }
+                                let mut tasks = (*tasks_done).clone();
+                                tasks.retain(|ticked| *ticked != task);
+                                if done {
+                                    tasks.push(task.clone());
+                                }
+                                tasks_done.set(tasks);
+                            })
+                        };
+                        html! {
+                            <Checkbox key={ task.name.clone() } text={ task.name.clone() } initial_value={ tasks_done.contains(&task.name) } callback={ task_ticked } />
+                        }
+                    })
+                }
                 //<button class="button outlined" ><span class="material-symbols-outlined">{ "add" }</span>{ " Schedule a new task" }</button>
-                if next_to_brew.1 != None {
+                if !last_brewed.0.is_empty() {
                     <hr/>
-                    <p><b>{ "Last coffee brewed:" }</b><br/>{
-                        (*last_brewed).0.to_string() +
-                        &(if let Some(p) = &(*last_brewed).1 {
-                            " & ".to_owned() + &p.to_string()
-                        } else {
-                            "".to_owned()
-                        })
-                    }</p>
+                    <p><b>{ "Last coffee brewed:" }</b><br/>{ last_brewed.tags(&catalog) }</p>
                 }
             }
+            <hr/>
+            <UrnTracker />
         </>
     }
 }
 
 #[derive(Properties, PartialEq)]
 pub struct DaydotData {
-    date:Date<Local>
+    at:DateTime<Local>,
+    #[prop_or(true)]
+    show_time:bool
 }
 
 #[function_component]
 pub fn Daydot(data:&DaydotData) -> Html {
-    let weekday = data.date.weekday();
+    let weekday = data.at.weekday();
     let mut weekday_str:String = String::new();
     match weekday {
         Weekday::Mon => {
@@ -306,57 +594,56 @@ pub fn Daydot(data:&DaydotData) -> Html {
         }
     }
 
-    let date = data.date.month().to_string() + "/" + &data.date.day().to_string();
+    let date = data.at.month().to_string() + "/" + &data.at.day().to_string();
     html! {
         <div class="daydot">
         <div class={ "daydot_day ".to_owned() + &weekday_str.to_lowercase() }>
         <b><p>{ weekday_str }</p></b>
         </div>
         <p>{ date }</p>
+        if data.show_time {
+            <p class="daydot_time">{ data.at.format("%-I:%M %p").to_string() }</p>
+        }
         </div>
     }
 }
 
-#[derive(PartialEq, Clone)]
-pub struct DaydotProduct(String, u8);
-
-#[derive(Properties, PartialEq)]
-pub struct DaydotCardData {
-    pub hb_products:Vec<DaydotProduct>,
-    pub cbs_products:Vec<DaydotProduct>
-}
-
 #[function_component]
-pub fn Daydots(data:&DaydotCardData) -> Html {
-    let today = Local::today();
-    let cbs_dates = use_state(|| {
-        let mut c = Vec::new();
-        for product in &data.cbs_products {
-            c.push(
-                html! {
-                    <div class="date_card">
-                    <h3>{ product.0.clone() }</h3>
-                    <Daydot date={ today + Duration::days(product.1.into())} />
-                    </div>
-                }
-            );
-        }
-        c
-    });
-    let hb_dates = use_state(|| {
-        let mut h = Vec::new();
-        for product in &data.hb_products {
-            h.push(
-                html! {
-                    <div class="date_card">
-                    <h3>{ product.0.clone() }</h3>
-                    <Daydot date={ today + Duration::days(product.1.into())} />
-                    </div>
-                }
-            );
+pub fn Daydots() -> Html {
+    // Dates go by the minute the card is looking at, so an open card keeps up as the day goes on.
+    let now = use_state_eq(current_minute);
+    {
+        let now = now.clone();
+        use_effect_with_deps(move |_| {
+            let interval = Interval::new(1000, move || now.set(current_minute()));
+            move || drop(interval)
+        }, ());
+    }
+    let now = *now;
+    let catalog = use_state(|| ProductCatalog::load(&*storage::backend()));
+    let show_catalog_editor = use_state(|| false);
+    let date_card = |product: &DaydotProduct, show_station: bool| {
+        let dates = product.expiry.dates(now, catalog.close_hour);
+        let labelled = dates.len() > 1;
+        html! {
+            <div class="date_card">
+            <h3>{ product.name.clone() }</h3>
+            if show_station {
+                <p class="daydot_label">{ product.station.name() }</p>
+            }
+            {
+                for dates.into_iter().map(|(label, at)| html! {
+                    <>
+                    if labelled {
+                        <p class="daydot_label">{ label }</p>
+                    }
+                    <Daydot at={ at } />
+                    </>
+                })
+            }
+            </div>
         }
-        h
-    });
+    };
 
     let hb_dates_shown = use_state(|| false);
     let cbs_dates_shown = use_state(|| false);
@@ -375,105 +662,94 @@ pub fn Daydots(data:&DaydotCardData) -> Html {
         })
     };
 
-    let search_results = use_state(Vec::<Html>::new);
+    let toggle_catalog_editor = {
+        let show_catalog_editor = show_catalog_editor.clone();
+        Callback::from(move |_| show_catalog_editor.set(!*show_catalog_editor))
+    };
+    let catalog_changed = {
+        let catalog = catalog.clone();
+        Callback::from(move |edited: ProductCatalog| catalog.set(edited))
+    };
+
+    let search = use_state(String::new);
     let search_results_ref = use_node_ref();
     let search_results_changed = {
         let search_results_ref = search_results_ref.clone();
-        let search_results = search_results.clone();
-        let h = data.hb_products.clone();
-        let c = data.cbs_products.clone();
+        let search = search.clone();
         Callback::<InputEvent>::from(move |_| {
-            let search = search_results_ref.cast::<HtmlInputElement>().expect("Search not found.");
-            if !search.value().is_empty() {
-                let mut joined_vec = h.clone();
-                joined_vec.append(&mut c.clone());
-
-                let mut results = Vec::<DaydotProduct>::new();
-
-                for p in joined_vec {
-                    let p_name = p.0.to_lowercase();
-                    let split = p_name.split(' ');
-                    for name in split {
-                        if name.starts_with(&search.value().to_lowercase()) {
-                            let mut product_already_exists:bool = false;
-                            for i in &results {
-                                if i.0 == p.0 {
-                                    product_already_exists = true;
-                                    break;
-                                }
-                            }
-                            if product_already_exists {
-                                continue;
-                            }
-                            results.push(p);
-                            break;
-                        }
-                    }
-                }
-
-                let mut h = Vec::new();
-                for product in results {
-                    h.push(
-                        html! {
-                            <div class="date_card">
-                            <h3>{ product.0.clone() }</h3>
-                            <Daydot date={ today + Duration::days(product.1.into())} />
-                            </div>
-                        }
-                    );
-                }
-                search_results.set(h);
-            }
-            else {
-                search_results.set(Vec::new());
-            }
-    })
+            let input = search_results_ref.cast::<HtmlInputElement>().expect("Search not found.");
+            search.set(input.value().to_lowercase());
+        })
     };
+    // Products whose name has a word starting with the search. A product both bars keep the same way shows up once,
+    // one they date differently is shown for each bar.
+    let mut search_results = Vec::<&DaydotProduct>::new();
+    if !search.is_empty() {
+        for product in &catalog.products {
+            let matches = product.name.to_lowercase().split(' ').any(|word| word.starts_with(search.as_str()));
+            if matches && !search_results.iter().any(|result| result.name == product.name && result.expiry == product.expiry) {
+                search_results.push(product);
+            }
+        }
+    }
+    let shared_name = |product: &DaydotProduct| search_results.iter().filter(|result| result.name == product.name).count() > 1;
     html! {
         <>
         <h2 class="title_white">{ "Daydots" }</h2>
-        <p><b>{ "Today is "}</b><Daydot date={chrono::Local::today()} /></p>
+        <p><b>{ "Today is "}</b><Daydot at={ now } show_time=false /></p>
         <span class="material-symbols-outlined" style="font-size:1.5rem; display:inline;">{ "search" }</span><input ref={search_results_ref} oninput={search_results_changed} class="text_input" size="1" type="text" placeholder="Search" />
-        if !(*search_results).is_empty() {
+        if !search_results.is_empty() {
             <h3 class="" >{"Search Results"}</h3>
             <div class="date_grid">
             {
-                (*search_results).clone()
+                for search_results.iter().map(|product| date_card(product, shared_name(product)))
             }
             </div>
             <hr />
         }
-        <h2 class="clickable" onclick={toggle_hb_dates_shown} >{"Hot Bar "} <span class="material-symbols-outlined">{ "coffee" }</span><span class="material-symbols-outlined">{ if *hb_dates_shown { "expand_less" } else { "expand_more" } }</span></h2>
+        <h2 class="clickable" onclick={toggle_hb_dates_shown} >{ Station::HotBar.name() }{ " " }<span class="material-symbols-outlined">{ Station::HotBar.icon() }</span><span class="material-symbols-outlined">{ if *hb_dates_shown { "expand_less" } else { "expand_more" } }</span></h2>
         if *hb_dates_shown {
             <div class="date_grid">
             {
-                (*hb_dates).clone()
+                for catalog.at(Station::HotBar).map(|product| date_card(product, false))
             }
             </div>
         }
-        <h2 class="clickable" onclick={toggle_cbs_dates_shown} >{"Cold Bar "} <span class="material-symbols-outlined">{ "blender" }</span><span class="material-symbols-outlined">{ if *cbs_dates_shown { "expand_less" } else { "expand_more" } }</span></h2>
+        <h2 class="clickable" onclick={toggle_cbs_dates_shown} >{ Station::ColdBar.name() }{ " " }<span class="material-symbols-outlined">{ Station::ColdBar.icon() }</span><span class="material-symbols-outlined">{ if *cbs_dates_shown { "expand_less" } else { "expand_more" } }</span></h2>
         if *cbs_dates_shown {
             <div class="date_grid">
             {
-                (*cbs_dates).clone()
+                for catalog.at(Station::ColdBar).map(|product| date_card(product, false))
             }
             </div>
         }
+        <hr/>
+        <a class="settings_toggle clickable" onclick={toggle_catalog_editor}>
+            <span class="material-symbols-outlined">{ "inventory_2" }</span>{ " Manage products" }
+        </a>
+        if *show_catalog_editor {
+            <ProductCatalogEditor catalog={ (*catalog).clone() } on_change={ catalog_changed } />
+        }
         </>
     }
 }
 
+fn current_minute() -> DateTime<Local> {
+    let now = clock::local_now();
+    now.with_second(0).and_then(|now| now.with_nanosecond(0)).unwrap_or(now)
+}
+
 #[function_component]
 pub fn DailyTasks() -> Html {
-    let tasks:UseStateHandle<Tasks> = use_state(Tasks::load);
+    let tasks:UseStateHandle<Tasks> = use_state(|| Tasks::load(&*storage::backend()));
     {
         let tasks = tasks.clone();
         let destructor_tasks_ref = tasks.clone();
         use_effect_with_deps(move |_| {
-            let listener = gloo::events::EventListener::new(&window().unwrap(), "beforeunload", move |_| { (*tasks).clone().save(); });
+            let listener = gloo::events::EventListener::new(&window().unwrap(), "beforeunload", move |_| { tasks.persist(); });
             move || {
                 drop(listener);
-                (*destructor_tasks_ref).clone().save();
+                destructor_tasks_ref.persist();
             }
         }, ());
     }
@@ -497,35 +773,210 @@ pub fn DailyTasks() -> Html {
     }
 }
 
-#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
-pub enum CardType {
-    StartNewTask,
-    Daydots,
-    CsCycle,
-    Tasks,
+#[function_component]
+pub fn Backup() -> Html {
+    let files = use_files();
+    let pending_import = use_state(|| None::<Rc<Bundle>>);
+    let message = use_state(|| None::<String>);
+    let file_input_ref = use_node_ref();
+
+    let export_data = {
+        let files = files.clone();
+        let message = message.clone();
+        Callback::from(move |_| {
+            let bundle = Bundle::export(&*storage::backend());
+            match serde_json::to_string_pretty(&bundle) {
+                Ok(json) => files.download(&format!("star-backup-{}.json", bundle.exported_at.format("%Y-%m-%d")), "application/json", &json),
+                Err(err) => message.set(Some(format!("Could not export data: {}", err))),
+            }
+        })
+    };
+
+    let file_chosen = {
+        let file_input_ref = file_input_ref.clone();
+        let files = files.clone();
+        let pending_import = pending_import.clone();
+        let message = message.clone();
+        Callback::from(move |_: Event| {
+            let input = file_input_ref.cast::<HtmlInputElement>().expect("File input not found.");
+            let pending_import = pending_import.clone();
+            let message = message.clone();
+            files.read_chosen(&input, move |result| {
+                match result.and_then(|text| Bundle::parse(&text).map_err(|err| err.to_string())) {
+                    Ok(bundle) => {
+                        pending_import.set(Some(Rc::new(bundle)));
+                        message.set(None);
+                    },
+                    Err(err) => {
+                        pending_import.set(None);
+                        message.set(Some(format!("This file can't be imported: {}", err)));
+                    }
+                }
+            });
+        })
+    };
+
+    let import_data = {
+        let pending_import = pending_import.clone();
+        let message = message.clone();
+        Callback::from(move |mode: ImportMode| {
+            if let Some(bundle) = &*pending_import {
+                // Nothing may be saved over the import until the page has reloaded with it.
+                storage::freeze();
+                let message = message.clone();
+                bundle.import(&*storage::backend(), mode, move |result| match result {
+                    Ok(()) => {
+                        // Reload so every card picks up the imported data.
+                        if let Some(window) = window() {
+                            if let Err(err) = window.location().reload() {
+                                log::error!("{:?}", err);
+                            }
+                        }
+                    },
+                    Err(err) => {
+                        storage::thaw();
+                        message.set(Some(format!("Could not import data, nothing was changed: {}", err)));
+                    },
+                });
+            }
+        })
+    };
+    let merge = {
+        let import_data = import_data.clone();
+        Callback::from(move |_| import_data.emit(ImportMode::Merge))
+    };
+    let replace = Callback::from(move |_| import_data.emit(ImportMode::Replace));
+    let cancel = {
+        let pending_import = pending_import.clone();
+        Callback::from(move |_| pending_import.set(None))
+    };
+
+    html! {
+        <>
+            <p>{ "Save everything Star keeps on this device to a file, or load it from a backup." }</p>
+            <button class="button" onclick={export_data}><span class="material-symbols-outlined icon">{ "download" }</span>{ " Export data" }</button>
+            <p><b>{ "Import data" }</b></p>
+            <input ref={file_input_ref} onchange={file_chosen} type="file" accept="application/json,.json" />
+            if let Some(message) = &*message {
+                <p>{ message.clone() }</p>
+            }
+            if let Some(bundle) = &*pending_import {
+                <hr/>
+                <p><b>{ "Backup from " }{ bundle.exported_at.with_timezone(&Local).format("%-m/%-d %-I:%M %p").to_string() }</b></p>
+                {
+                    for bundle.diff(&*storage::backend()).into_iter().map(|entry| {
+                        let change = match entry.change {
+                            Change::Added => "New",
+                            Change::Changed => "Different from this device",
+                            Change::Unchanged => "Same as this device",
+                            Change::LocalOnly => "Only on this device, kept when merging",
+                        };
+                        html! {
+                            <p>{ entry.label }{ ": " }{ change }</p>
+                        }
+                    })
+                }
+                <button class="button" onclick={merge}>{ "Merge" }</button>
+                { " " }
+                <button class="button outlined" onclick={replace}>{ "Replace" }</button>
+                { " " }
+                <button class="button outlined" onclick={cancel}>{ "Cancel" }</button>
+            }
+        </>
+    }
+}
+
+// Names a kind of card by its registry key, see cards::REGISTRY. Kept as the key string so
+// saved cards still load if a kind is removed, they just render as an invalid card.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CardType(Cow<'static, str>);
+
+impl CardType {
+    pub const START_NEW_TASK: CardType = CardType::new(cards::Launcher::KEY);
+
+    pub const fn new(key:&'static str) -> Self {
+        CardType(Cow::Borrowed(key))
+    }
+
+    pub fn key(&self) -> &str {
+        &self.0
+    }
+
+    pub fn kind(&self) -> Option<&'static dyn CardKind> {
+        cards::kind(self)
+    }
+
+    pub fn name(&self) -> &str {
+        self.kind().map_or(self.key(), |kind| kind.name())
+    }
+
+    pub fn allows_multiple(&self) -> bool {
+        self.kind().is_some_and(|kind| kind.allows_multiple())
+    }
+
+    pub fn closable(&self) -> bool {
+        self.kind().is_none_or(|kind| kind.closable())
+    }
+}
+
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct CardId(pub u64);
+
+impl CardId {
+    pub fn generate() -> CardId {
+        CardId(clock::unique_id())
+    }
 }
 
+impl std::fmt::Display for CardId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Version 1 -> 2: cards get a persisted id instead of the index they were rendered at.
+pub fn assign_card_ids(mut cards: Value) -> Result<Value, String> {
+    for card in cards.as_array_mut().ok_or("cards are not a list")? {
+        let card = card.as_object_mut().ok_or("card is not an object")?;
+        card.remove("index");
+        card.entry("id").or_insert_with(|| Value::from(CardId::generate().0));
+    }
+    Ok(cards)
+}
 
 #[derive(Properties, PartialEq, Clone, Serialize, Deserialize, Debug)]
 pub struct CardData {
-    #[prop_or(CardType::StartNewTask)]
+    #[prop_or(CardType::START_NEW_TASK)]
     pub card_type:CardType,
     #[prop_or_default]
     pub is_priority:bool,
-    pub index:usize,
+    pub id:CardId,
+    // Tells instances of the same card type apart.
+    #[serde(default)]
+    #[prop_or_default]
+    pub label:Option<String>,
     #[serde(skip)]
     pub create_card:Callback<CardType>,
     #[serde(skip)]
     pub add_priority_card:Callback<CardType>,
     #[serde(skip)]
-    pub destroy_priority:Callback<usize>,
+    pub destroy_priority:Callback<CardId>,
+    #[serde(skip)]
+    pub destroy_card:Callback<CardId>,
+    #[serde(skip)]
+    pub rename_card:Callback<(CardId, Option<String>)>,
     #[serde(skip)]
-    pub destroy_card:Callback<usize>,
+    pub move_card:Callback<CardMove>,
+    #[serde(skip)]
+    #[prop_or_default]
+    pub drop_hint:Option<DropTarget>,
 }
 
 impl CardData {
     pub fn new(card_type:CardType) -> Self {
-        CardData { card_type, is_priority: false, index:0, create_card:Callback::noop(), add_priority_card:Callback::noop(), destroy_priority:Callback::noop(), destroy_card:Callback::noop() }
+        CardData { card_type, is_priority: false, id:CardId::generate(), label:None, create_card:Callback::noop(), add_priority_card:Callback::noop(), destroy_priority:Callback::noop(), destroy_card:Callback::noop(), rename_card:Callback::noop(), move_card:Callback::noop(), drop_hint:None }
     }
 
     pub fn new_priority(card_type:CardType) -> Self {
@@ -535,88 +986,19 @@ impl CardData {
     }
 
     pub fn get_title(&self) -> String {
-        match self.card_type {
-            CardType::StartNewTask => "What can I help you with today?".to_string(),
-            CardType::CsCycle => String::new(),
-            CardType::Daydots => String::new(),
-            CardType::Tasks => "Tasks".to_string(),
-            _ => "Invalid Card".to_string(),
+        if let Some(label) = &self.label {
+            return label.clone();
+        }
+        match self.card_type.kind() {
+            Some(kind) => kind.title().to_string(),
+            None => "Invalid Card".to_string(),
         }
     }
 
     pub fn get_content(&self) -> Html {
-        match self.card_type {
-            CardType::StartNewTask => {
-                let create_cs_cycle = {
-                    let set_priority_card = self.add_priority_card.clone();
-                    Callback::from(move |_| {
-                        set_priority_card.emit(CardType::CsCycle);
-                    })
-                };
-                let create_daydot_card = {
-                    let set_priority_card = self.add_priority_card.clone();
-                    Callback::from(move |_| {
-                        set_priority_card.emit(CardType::Daydots);
-                    })
-                };
-                let create_tasks_card = {
-                    let create_card = self.add_priority_card.clone();
-                    Callback::from(move |_| {
-                        create_card.emit(CardType::Tasks);
-                    })
-                };
-
-                html! {
-                    <div class="card-multioption">
-                    <a class="card-multioption_button" onclick={ create_cs_cycle }>
-                    <span class="icon material-symbols-outlined">{ "update" }</span>
-                    { "Start a new CS cycle" }
-                    </a>
-                    <a class="card-multioption_button" onclick={ create_daydot_card }>
-                    <span class="icon material-symbols-outlined">{ "event" }</span>
-                    { "Daydot some backups" }
-                    </a>
-                    <a class="card-multioption_button" onclick={ create_tasks_card }>
-                    <span class="icon material-symbols-outlined">{ "checklist" }</span>
-                    { "View my tasks for today" }
-                    </a>
-                    </div>
-                }
-            },
-            CardType::CsCycle => {
-                let data = CsData::load();
-                html! { <CsCycle ..data /> }
-            },
-            CardType::Daydots => {
-                let hb_products = vec!(
-                    DaydotProduct(String::from("Mocha"), 1 ),
-                    DaydotProduct(String::from("White Mocha"), 14),
-                    DaydotProduct(String::from("Chai"), 1 ),
-                    DaydotProduct(String::from("Whipped Cream"), 1),
-                );
-                let cb_products = vec!(
-                    DaydotProduct(String::from("Refresher Base"), 3),
-                    DaydotProduct(String::from("Refresher Fruit Inclusions"), 5),
-                    DaydotProduct(String::from("Lemonade"), 2),
-                    DaydotProduct(String::from("Vanilla Sweet Cream"), 2 ),
-                    DaydotProduct(String::from("Whipped Cream"), 1 ),
-                    DaydotProduct(String::from("Frap Roast"), 2 ),
-                    DaydotProduct(String::from("Frap Chips"), 7),
-                    DaydotProduct(String::from("Mocha"), 1),
-                    DaydotProduct(String::from("Cold Brew"), 7),
-                    DaydotProduct(String::from("Powder Inclusions"), 7),
-                    DaydotProduct(String::from("Caramel Drizzle"), 14),
-                );
-                html! {
-                    <Daydots hb_products={hb_products} cbs_products={cb_products} />
-                }
-            }
-            CardType::Tasks => {
-                html! {
-                    <DailyTasks />
-                }
-            }
-            _ => {
+        match self.card_type.kind() {
+            Some(kind) => kind.content(self),
+            None => {
                 html! {
                     <></>
                 }
@@ -625,39 +1007,185 @@ impl CardData {
     }
 }
 
+// What a card's drag handle asks the column to do.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CardMove {
+    DragStart(CardId),
+    Hover(CardId, Option<DropTarget>),
+    Drop(CardId, DropTarget),
+    DragEnd,
+    Step(CardId, bool),
+    Promote(CardId),
+    Demote(CardId),
+}
+
+fn drop_target_at(x:i32, y:i32) -> Option<DropTarget> {
+    let element = gloo::utils::document().element_from_point(x as f32, y as f32)?;
+    if let Some(card) = element.closest("[data-card-id]").ok().flatten() {
+        let id = CardId(card.get_attribute("data-card-id")?.parse().ok()?);
+        let rect = card.get_bounding_client_rect();
+        return Some(DropTarget::Card { id, after: f64::from(y) > rect.top() + rect.height() / 2.0 });
+    }
+    let zone = element.closest("[data-drop-lane]").ok().flatten()?;
+    match zone.get_attribute("data-drop-lane")?.as_str() {
+        "priority" => Some(DropTarget::Lane(Lane::Priority)),
+        "normal" => Some(DropTarget::Lane(Lane::Normal)),
+        _ => None,
+    }
+}
+
 #[function_component]
 pub fn Card(data:&CardData) -> Html {
     let destroy_card = {
-        let index = data.index;
+        let id = data.id;
         let destroy_card = data.destroy_card.clone();
         Callback::from(move |_| {
-            destroy_card.emit(index);
+            destroy_card.emit(id);
         })
     };
     let destroy_priority = {
-        let index = data.index;
+        let id = data.id;
         let destroy_priority = data.destroy_priority.clone();
         Callback::from(move |_| {
-            destroy_priority.emit(index);
+            destroy_priority.emit(id);
         })
     };
 
+    // Dragging uses pointer events, so mouse, touch and pen all work the same way.
+    let dragging = use_state(|| false);
+    let drag_start = {
+        let id = data.id;
+        let dragging = dragging.clone();
+        let move_card = data.move_card.clone();
+        Callback::from(move |event:PointerEvent| {
+            if let Some(handle) = event.target().and_then(|target| target.dyn_into::<Element>().ok()) {
+                if let Err(err) = handle.set_pointer_capture(event.pointer_id()) {
+                    log::error!("{:?}", err);
+                }
+            }
+            event.prevent_default();
+            dragging.set(true);
+            move_card.emit(CardMove::DragStart(id));
+        })
+    };
+    let drag_move = {
+        let id = data.id;
+        let dragging = dragging.clone();
+        let move_card = data.move_card.clone();
+        Callback::from(move |event:PointerEvent| {
+            if *dragging {
+                move_card.emit(CardMove::Hover(id, drop_target_at(event.client_x(), event.client_y())));
+            }
+        })
+    };
+    let drag_end = {
+        let id = data.id;
+        let dragging = dragging.clone();
+        let move_card = data.move_card.clone();
+        Callback::from(move |event:PointerEvent| {
+            if !*dragging {
+                return;
+            }
+            dragging.set(false);
+            match drop_target_at(event.client_x(), event.client_y()) {
+                Some(target) if event.type_() == "pointerup" => move_card.emit(CardMove::Drop(id, target)),
+                _ => move_card.emit(CardMove::DragEnd),
+            }
+        })
+    };
+    let key_down = {
+        let id = data.id;
+        let move_card = data.move_card.clone();
+        Callback::from(move |event:KeyboardEvent| {
+            let card_move = match (event.key().as_str(), event.shift_key()) {
+                ("ArrowUp", true) => CardMove::Promote(id),
+                ("ArrowDown", true) => CardMove::Demote(id),
+                ("ArrowUp", false) => CardMove::Step(id, true),
+                ("ArrowDown", false) => CardMove::Step(id, false),
+                _ => return,
+            };
+            event.prevent_default();
+            move_card.emit(card_move);
+            // Moving the card re-inserts its element, which drops focus from the handle.
+            if let Some(handle) = event.target().and_then(|target| target.dyn_into::<HtmlElement>().ok()) {
+                Timeout::new(0, move || {
+                    if let Err(err) = handle.focus() {
+                        log::error!("{:?}", err);
+                    }
+                }).forget();
+            }
+        })
+    };
+
+    let renaming = use_state(|| false);
+    let label_input_ref = use_node_ref();
+    let start_renaming = {
+        let renaming = renaming.clone();
+        Callback::from(move |_| renaming.set(true))
+    };
+    let label_changed = {
+        let id = data.id;
+        let renaming = renaming.clone();
+        let rename_card = data.rename_card.clone();
+        let label_input_ref = label_input_ref.clone();
+        Callback::from(move |_:FocusEvent| {
+            if let Some(input) = label_input_ref.cast::<HtmlInputElement>() {
+                let label = input.value().trim().to_string();
+                rename_card.emit((id, if label.is_empty() { None } else { Some(label) }));
+            }
+            renaming.set(false);
+        })
+    };
+    let label_key_down = {
+        let label_input_ref = label_input_ref.clone();
+        Callback::from(move |event:KeyboardEvent| {
+            if event.key() == "Enter" {
+                if let Some(input) = label_input_ref.cast::<HtmlInputElement>() {
+                    if let Err(err) = input.blur() {
+                        log::error!("{:?}", err);
+                    }
+                }
+            }
+        })
+    };
+    // Focus the label input as soon as it shows up.
+    {
+        let label_input_ref = label_input_ref.clone();
+        use_effect_with_deps(move |renaming| {
+            if *renaming {
+                if let Some(input) = label_input_ref.cast::<HtmlInputElement>() {
+                    if let Err(err) = input.focus() {
+                        log::error!("{:?}", err);
+                    }
+                }
+            }
+            || ()
+        }, *renaming);
+    }
+
+    let drop_class = match data.drop_hint {
+        Some(DropTarget::Card { after: false, .. }) => Some("drop_before"),
+        Some(DropTarget::Card { after: true, .. }) => Some("drop_after"),
+        _ => None,
+    };
     html! {
-        if data.is_priority {
-            <div class="card filled">
-                <a class="card_close_button" onclick={destroy_priority}><span class="material-symbols-outlined">{ "close" }</span></a>
+        <div class={ classes!("card", if data.is_priority { "filled" } else { "elevated" }, (*dragging).then_some("dragging"), drop_class) } data-card-id={ data.id.to_string() } tabindex="-1">
+            if data.card_type.closable() {
+                <a class="card_close_button" onclick={ if data.is_priority { destroy_priority } else { destroy_card } }><span class="material-symbols-outlined">{ "close" }</span></a>
+            }
+            <button class="card_drag_handle material-symbols-outlined" title="Drag to move. Arrow keys move up and down, Shift+Up makes it a priority and Shift+Down moves it back."
+                onpointerdown={drag_start} onpointermove={drag_move} onpointerup={drag_end.clone()} onpointercancel={drag_end} onkeydown={key_down}>
+                { "drag_indicator" }
+            </button>
+            if *renaming {
+                <input ref={label_input_ref} onblur={label_changed} onkeydown={label_key_down} value={ data.label.clone().unwrap_or_default() } class="text_input" size="1" type="text" placeholder={ data.card_type.name().to_string() } />
+            } else if data.card_type.allows_multiple() {
+                <h2 class="title clickable" onclick={start_renaming} title="Rename">{ data.get_title() }<span class="material-symbols-outlined">{ " edit" }</span></h2>
+            } else {
                 <h2 class="title">{ data.get_title() }</h2>
-                { data.get_content() }
-            </div>
-        } else {
-        <div class="card elevated">
-            if data.card_type != CardType::StartNewTask {
-                <a class="card_close_button" onclick={destroy_card}><span class="material-symbols-outlined">{ "close" }</span></a>
             }
-            <h2 class="title">{ data.get_title() }</h2>
             { data.get_content() }
         </div>
-        }
     }
 }
 
@@ -681,7 +1209,7 @@ pub fn Checkbox(data:&CheckboxData) -> Html {
         let callback = data.callback.clone();
         Callback::from(move |_| {
             state.set(!*state);
-            callback.emit(*state);
+            callback.emit(!*state);
         })
     };
     html! {
@@ -715,3 +1243,53 @@ pub fn Checkbox(data:&CheckboxData) -> Html {
 //     html! {
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryBackend;
+
+    #[test]
+    fn the_first_cycle_card_takes_over_the_shared_cycle() {
+        let backend = MemoryBackend::default();
+        CsData { start_time_value: 1200, ..CsData::load(&backend) }.save(&backend).unwrap();
+
+        assert_eq!(CsData::load_for_card(&backend, CardId(1)).start_time_value, 1200);
+        assert!(backend.get(CS_CYCLE.key).is_none());
+        assert!(backend.get(&CS_CYCLE.instance_key(CardId(1))).is_some());
+        assert_eq!(CsData::load_for_card(&backend, CardId(2)).start_time_value, 1800);
+        assert_eq!(CsData::load_for_card(&backend, CardId(1)).start_time_value, 1200);
+    }
+
+    #[test]
+    fn running_cycles_carry_on_from_their_stored_seconds() {
+        clock::fake_local(2024, 3, 5, 9, 0);
+        let running = CS_CYCLE.upgrade(r#"{"cycle_state":"Started","current_cycle_time":600,"start_time_value":1800,"last_brewed":["Pike",null]}"#).unwrap();
+        assert_eq!(running.deadline, Some(clock::now() + Duration::seconds(600)));
+        let stopped = CS_CYCLE.upgrade(r#"{"cycle_state":"NotStarted","current_cycle_time":1800,"start_time_value":1800,"last_brewed":["Pike",null]}"#).unwrap();
+        assert_eq!(stopped.cycle_state, CsState::NotStarted);
+        assert_eq!(stopped.deadline, None);
+    }
+
+    #[test]
+    fn paused_cycles_stay_paused_across_reloads() {
+        let clock = clock::fake_local(2024, 3, 5, 9, 0);
+        let backend = MemoryBackend::default();
+        let paused_at = clock::now();
+        let deadline = paused_at + Duration::seconds(500);
+        CsData { cycle_state: CsState::Started, deadline: Some(deadline), paused_at: Some(paused_at), paused_seconds: 60, ..CsData::load(&backend) }.save(&backend).unwrap();
+
+        clock.advance(Duration::minutes(10));
+        let loaded = CsData::load(&backend);
+        assert_eq!((loaded.deadline, loaded.paused_at, loaded.paused_seconds), (Some(deadline), Some(paused_at), 60));
+        assert_eq!(TimerData::seconds_between(loaded.paused_at.unwrap(), deadline), 500);
+    }
+
+    #[test]
+    fn brews_stored_by_name_become_roast_ids() {
+        let baseline = |brewed: &str| CS_CYCLE.upgrade(&format!(r#"{{"cycle_state":"NotStarted","current_cycle_time":1800,"start_time_value":1800,"last_brewed":{}}}"#, brewed)).unwrap().last_brewed;
+        assert_eq!(baseline(r#"["Pike",null]"#), CoffeesToBrew(vec![roasts::PIKE]));
+        assert_eq!(baseline(r#"["Blonde","Dark"]"#), CoffeesToBrew(vec![roasts::BLONDE, roasts::DARK]));
+        assert_eq!(baseline(r#"["Decaf",null]"#), CoffeesToBrew(Vec::new()));
+    }
+}
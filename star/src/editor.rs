@@ -0,0 +1,35 @@
+use yew::prelude::*;
+
+use crate::storage::StorableData;
+
+// A change to one of the lists the settings editors show, like the checklist or the rotation rules.
+pub type ListChange<T> = Box<dyn Fn(&mut Vec<T>)>;
+
+// Applies change to a copy of value, saves it and passes it on to on_change.
+pub fn save_edit<D: StorableData + Clone + 'static, E>(value: &D, on_change: &Callback<D>, change: impl Fn(&mut D) + 'static) -> Callback<E> {
+    let value = value.clone();
+    let on_change = on_change.clone();
+    Callback::from(move |_| {
+        let mut value = value.clone();
+        change(&mut value);
+        value.persist();
+        on_change.emit(value);
+    })
+}
+
+// Move up, move down and remove buttons for the row at index, edit turns each change into a click handler.
+pub fn row_buttons<T: 'static>(index: usize, count: usize, edit: impl Fn(ListChange<T>) -> Callback<MouseEvent>) -> Html {
+    html! {
+        <>
+            <button class="button outlined" title="Move up" disabled={ index == 0 } onclick={ edit(Box::new(move |list| list.swap(index - 1, index))) }>
+                <span class="material-symbols-outlined">{ "arrow_upward" }</span>
+            </button>
+            <button class="button outlined" title="Move down" disabled={ index + 1 == count } onclick={ edit(Box::new(move |list| list.swap(index, index + 1))) }>
+                <span class="material-symbols-outlined">{ "arrow_downward" }</span>
+            </button>
+            <button class="button outlined" title="Remove" onclick={ edit(Box::new(move |list| { list.remove(index); })) }>
+                <span class="material-symbols-outlined">{ "delete" }</span>
+            </button>
+        </>
+    }
+}
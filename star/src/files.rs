@@ -0,0 +1,42 @@
+use gloo::file::{ObjectUrl, callbacks::FileReader};
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlAnchorElement, HtmlInputElement};
+use yew::prelude::*;
+
+// Keeps the URL of the last download and the reader of the last upload alive until the browser is done with them.
+#[derive(Clone)]
+pub struct FileHandle {
+    download_url: UseStateHandle<Option<ObjectUrl>>,
+    file_reader: UseStateHandle<Option<FileReader>>,
+}
+
+impl FileHandle {
+    // Has the browser save the contents as a file.
+    pub fn download(&self, file_name: &str, mime_type: &str, contents: &str) {
+        let url = ObjectUrl::from(gloo::file::Blob::new_with_options(contents, Some(mime_type)));
+        if let Ok(anchor) = gloo::utils::document().create_element("a") {
+            let anchor: HtmlAnchorElement = anchor.unchecked_into();
+            anchor.set_href(&url);
+            anchor.set_download(file_name);
+            anchor.click();
+        }
+        self.download_url.set(Some(url));
+    }
+
+    // Reads the file chosen in input as text, then clears the input so the same file can be picked again.
+    pub fn read_chosen(&self, input: &HtmlInputElement, on_read: impl FnOnce(Result<String, String>) + 'static) {
+        if let Some(file) = input.files().and_then(|files| files.get(0)) {
+            let reader = gloo::file::callbacks::read_as_text(&gloo::file::File::from(file), move |result| on_read(result.map_err(|err| err.to_string())));
+            self.file_reader.set(Some(reader));
+        }
+        input.set_value("");
+    }
+}
+
+#[hook]
+pub fn use_files() -> FileHandle {
+    FileHandle {
+        download_url: use_state(|| None),
+        file_reader: use_state(|| None),
+    }
+}
@@ -0,0 +1,222 @@
+use chrono::{Date, DateTime, Duration, Local, Timelike, Utc};
+use serde::{Serialize, Deserialize};
+use yew::prelude::*;
+
+use crate::{clock, state::{Daypart, TimerData}, storage::{self, Schema, SaveError, StorableData, StorageBackend}};
+
+// One finished CS cycle.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct CycleRecord {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    // The label of the card it ran on, when it has one.
+    pub cycle: Option<String>,
+    pub flex: bool,
+    pub duration: i32, // Seconds the cycle was set to run for
+    pub paused_seconds: i32,
+    // How far past its deadline the cycle was stopped, 0 when it finished in time.
+    pub overrun_seconds: i32,
+    pub brewed: Vec<String>,
+    pub tasks_done: Vec<String>,
+}
+
+impl CycleRecord {
+    pub fn day(&self) -> Date<Local> {
+        self.started_at.with_timezone(&Local).date()
+    }
+
+    pub fn daypart(&self) -> Daypart {
+        Daypart::at(self.started_at.with_timezone(&Local).hour())
+    }
+
+    pub fn ran_over(&self) -> bool {
+        self.overrun_seconds > 0
+    }
+}
+
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct CycleLog {
+    pub records: Vec<CycleRecord>,
+}
+
+// Older records are dropped when a new one is added, the log is for spotting recent trends.
+const KEEP_DAYS: i64 = 60;
+
+pub const CS_HISTORY: Schema<CycleLog> = Schema::new("cs_history", "CS cycle history", &[]);
+
+impl StorableData for CycleLog {
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self {
+        CS_HISTORY.load(backend).unwrap_or_default()
+    }
+
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        CS_HISTORY.save(backend, self)
+    }
+}
+
+impl CycleLog {
+    pub fn record(record: CycleRecord) {
+        let mut log = Self::load(&*storage::backend());
+        let cutoff = clock::now() - Duration::days(KEEP_DAYS);
+        log.records.retain(|record| record.started_at > cutoff);
+        log.records.push(record);
+        log.persist();
+    }
+
+    // Cycles started on a local day.
+    pub fn on_day(&self, day: Date<Local>) -> impl Iterator<Item = &CycleRecord> {
+        self.records.iter().filter(move |record| record.day() == day)
+    }
+}
+
+#[derive(Default)]
+struct Summary {
+    cycles: usize,
+    over: usize,
+    overrun_seconds: i32,
+}
+
+impl Summary {
+    fn of<'a>(records: impl Iterator<Item = &'a CycleRecord>) -> Self {
+        records.fold(Summary::default(), |mut summary, record| {
+            summary.cycles += 1;
+            if record.ran_over() {
+                summary.over += 1;
+                summary.overrun_seconds += record.overrun_seconds;
+            }
+            summary
+        })
+    }
+
+    fn on_time(&self) -> String {
+        match self.cycles {
+            0 => "-".to_string(),
+            cycles => format!("{}%", (cycles - self.over) * 100 / cycles),
+        }
+    }
+
+    fn average_overrun(&self) -> String {
+        match self.over {
+            0 => "-".to_string(),
+            over => TimerData::format_time_left(self.overrun_seconds / over as i32),
+        }
+    }
+}
+
+const STATS_DAYS: i64 = 7;
+
+#[function_component]
+pub fn CycleStats() -> Html {
+    let log = use_state(|| CycleLog::load(&*storage::backend()));
+    let today = clock::today();
+    let selected_day = use_state(|| today);
+    let days: Vec<Date<Local>> = (0..STATS_DAYS).map(|ago| today - Duration::days(ago)).collect();
+
+    let mut brews: Vec<(String, usize)> = Vec::new();
+    for roast in log.on_day(*selected_day).flat_map(|record| record.brewed.iter()) {
+        match brews.iter_mut().find(|(name, _)| name == roast) {
+            Some((_, count)) => *count += 1,
+            None => brews.push((roast.clone(), 1)),
+        }
+    }
+
+    html! {
+        <div class="cycle_stats">
+            <p><b>{ format!("Last {} days", STATS_DAYS) }</b></p>
+            <table>
+                <tr><th>{ "Day" }</th><th>{ "Cycles" }</th><th>{ "Over time" }</th><th>{ "On time" }</th><th>{ "Average overrun" }</th></tr>
+                {
+                    for days.iter().map(|day| {
+                        let summary = Summary::of(log.on_day(*day));
+                        let select = {
+                            let selected_day = selected_day.clone();
+                            let day = *day;
+                            Callback::from(move |_| selected_day.set(day))
+                        };
+                        html! {
+                            <tr class={ classes!("clickable", (*day == *selected_day).then_some("selected")) } onclick={select}>
+                                <td>{ if *day == today { "Today".to_string() } else { day.format("%a %b %-d").to_string() } }</td>
+                                <td>{ summary.cycles }</td>
+                                <td>{ summary.over }</td>
+                                <td>{ summary.on_time() }</td>
+                                <td>{ summary.average_overrun() }</td>
+                            </tr>
+                        }
+                    })
+                }
+            </table>
+            <p><b>{ format!("By daypart, {}", selected_day.format("%A %b %-d")) }</b></p>
+            <table>
+                <tr><th>{ "Daypart" }</th><th>{ "Cycles" }</th><th>{ "Over time" }</th><th>{ "On time" }</th><th>{ "Average overrun" }</th></tr>
+                {
+                    for [Daypart::Opening, Daypart::Mid, Daypart::Closing].into_iter().map(|daypart| {
+                        let summary = Summary::of(log.on_day(*selected_day).filter(|record| record.daypart() == daypart));
+                        html! {
+                            <tr>
+                                <td>{ daypart.to_string() }<br/><small>{ daypart.get_time_period() }</small></td>
+                                <td>{ summary.cycles }</td>
+                                <td>{ summary.over }</td>
+                                <td>{ summary.on_time() }</td>
+                                <td>{ summary.average_overrun() }</td>
+                            </tr>
+                        }
+                    })
+                }
+            </table>
+            <p><b>{ "Brewed" }</b></p>
+            if brews.is_empty() {
+                <p>{ "Nothing logged for this day." }</p>
+            } else {
+                <p>{ brews.iter().map(|(roast, count)| format!("{} x{}", roast, count)).collect::<Vec<_>>().join(", ") }</p>
+            }
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(started_at: DateTime<Utc>, overrun_seconds: i32) -> CycleRecord {
+        CycleRecord {
+            started_at,
+            ended_at: started_at + Duration::minutes(30),
+            cycle: None,
+            flex: false,
+            duration: 1800,
+            paused_seconds: 0,
+            overrun_seconds,
+            brewed: Vec::new(),
+            tasks_done: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn summaries_count_cycles_per_day_and_daypart() {
+        let clock = clock::fake_local(2024, 3, 5, 7, 0);
+        let morning = clock::now();
+        clock.advance(Duration::hours(6));
+        let afternoon = clock::now();
+        let log = CycleLog { records: vec![
+            record(morning - Duration::days(1), 600),
+            record(morning, 0),
+            record(morning + Duration::hours(1), 120),
+            record(afternoon, 0),
+            record(afternoon + Duration::minutes(30), 240),
+        ] };
+
+        let today = Summary::of(log.on_day(clock::today()));
+        assert_eq!((today.cycles, today.over), (4, 2));
+        assert_eq!(today.on_time(), "50%");
+        assert_eq!(today.average_overrun(), "03:00");
+
+        let opening = Summary::of(log.on_day(clock::today()).filter(|record| record.daypart() == Daypart::Opening));
+        assert_eq!((opening.cycles, opening.over), (2, 1));
+        let mid = Summary::of(log.on_day(clock::today()).filter(|record| record.daypart() == Daypart::Mid));
+        assert_eq!((mid.cycles, mid.over), (2, 1));
+        assert_eq!(mid.average_overrun(), "04:00");
+
+        let closing = Summary::of(log.on_day(clock::today()).filter(|record| record.daypart() == Daypart::Closing));
+        assert_eq!((closing.on_time(), closing.average_overrun()), ("-".to_string(), "-".to_string()));
+    }
+}
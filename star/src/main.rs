@@ -1,20 +1,110 @@
 use yew::prelude::*;
 
+mod alarm;
+mod cards;
+mod checklist;
+mod clock;
 mod state;
 mod components;
+mod editor;
+mod files;
+mod history;
+mod notifications;
+mod products;
+mod roasts;
+mod rotation;
 mod storage;
+mod timers;
+mod urns;
 use state::StarData;
 use state::StarAction;
+use state::{SaveFailures, SaveFailureAction, DropTarget};
 use components::*;
 
-use crate::storage::StorableData;
+use std::rc::Rc;
+
+use gloo::timers::callback::Timeout;
+use wasm_bindgen::JsCast;
+use web_sys::{window, HtmlInputElement, KeyboardEvent};
+
+use crate::storage::{StorableData, StorageBackend, BackendError, LocalBackend, IndexedDbBackend};
 
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+#[derive(Properties, PartialEq)]
+struct StarProps {
+    // Why IndexedDB couldn't be opened, while the app runs on LocalStorage instead.
+    storage_error: Option<BackendError>,
+}
+
 #[function_component]
-fn Star() -> Html {
-    let state = use_reducer(StarData::load);
+fn Star(props: &StarProps) -> Html {
+    let state = use_reducer(|| StarData::load(&*storage::backend()));
+    let save_failures = use_reducer_eq(SaveFailures::default);
+    {
+        let save_failures = save_failures.clone();
+        use_effect_with_deps(move |_| {
+            storage::set_save_listener(Some(Callback::from(move |event| save_failures.dispatch(SaveFailureAction::Event(event)))));
+            || storage::set_save_listener(None)
+        }, ());
+    }
+    let retry_saves = {
+        let save_failures = save_failures.clone();
+        Callback::from(move |_| save_failures.dispatch(SaveFailureAction::Retry))
+    };
+    let dismiss_save_failures = {
+        let save_failures = save_failures.clone();
+        Callback::from(move |_| save_failures.dispatch(SaveFailureAction::Dismiss))
+    };
+    let storage_error = use_state(|| props.storage_error.clone());
+    let dismiss_storage_error = {
+        let storage_error = storage_error.clone();
+        Callback::from(move |_| storage_error.set(None))
+    };
+    let dismiss_unreadable = {
+        let save_failures = save_failures.clone();
+        Callback::from(move |_| save_failures.dispatch(SaveFailureAction::DismissUnreadable))
+    };
+    // Undo and redo shortcuts, left alone while typing so inputs keep their own undo.
+    {
+        let state = state.clone();
+        use_effect_with_deps(move |_| {
+            let listener = gloo::events::EventListener::new(&window().unwrap(), "keydown", move |event| {
+                let event = match event.dyn_ref::<KeyboardEvent>() {
+                    Some(event) => event,
+                    None => return,
+                };
+                if event.target().and_then(|target| target.dyn_into::<HtmlInputElement>().ok()).is_some() {
+                    return;
+                }
+                if !(event.ctrl_key() || event.meta_key()) {
+                    return;
+                }
+                match event.key().to_lowercase().as_str() {
+                    "z" if event.shift_key() => state.dispatch(StarAction::Redo),
+                    "z" => state.dispatch(StarAction::Undo),
+                    "y" => state.dispatch(StarAction::Redo),
+                    _ => return,
+                }
+                event.prevent_default();
+            });
+            move || drop(listener)
+        }, ());
+    }
+    // Undo hints go away on their own after a few seconds.
+    {
+        let undo_hint = state.undo_hint.clone();
+        let state = state.clone();
+        use_effect_with_deps(move |undo_hint| {
+            let timeout = undo_hint.as_ref().map(|_| Timeout::new(6000, move || state.dispatch(StarAction::DismissUndoHint)));
+            move || drop(timeout)
+        }, undo_hint);
+    }
+    let undo = {
+        let state = state.clone();
+        Callback::from(move |_| state.dispatch(StarAction::Undo))
+    };
     let create_card = {
         let state = state.clone();
         Callback::from(move |card_type:CardType| state.dispatch(StarAction::AddCard(card_type)))
@@ -27,14 +117,63 @@ fn Star() -> Html {
 
     let destroy_priority = {
         let state = state.clone();
-        Callback::<usize>::from(move |index| state.dispatch(StarAction::DestroyPriorityCard(index)))
+        Callback::from(move |id:CardId| state.dispatch(StarAction::DestroyPriorityCard(id)))
     };
 
     let destroy_card = {
         let state = state.clone();
-        Callback::from(move |index:usize| state.dispatch(StarAction::DestroyCard(index)))
+        Callback::from(move |id:CardId| state.dispatch(StarAction::DestroyCard(id)))
     };
 
+    let rename_card = {
+        let state = state.clone();
+        Callback::from(move |(id, label):(CardId, Option<String>)| state.dispatch(StarAction::RenameCard(id, label)))
+    };
+
+    // The card being dragged and where it would land.
+    let drag = use_state_eq(|| None::<(CardId, Option<DropTarget>)>);
+    let move_card = {
+        let state = state.clone();
+        let drag = drag.clone();
+        Callback::from(move |card_move:CardMove| {
+            let target = match card_move {
+                CardMove::DragStart(id) => {
+                    drag.set(Some((id, None)));
+                    None
+                },
+                CardMove::Hover(id, target) => {
+                    drag.set(Some((id, target)));
+                    None
+                },
+                CardMove::DragEnd => {
+                    drag.set(None);
+                    None
+                },
+                CardMove::Drop(id, target) => {
+                    drag.set(None);
+                    state.drop_position(id, target).map(|position| (id, position))
+                },
+                CardMove::Step(id, up) => state.step_position(id, up).map(|position| (id, position)),
+                CardMove::Promote(id) => {
+                    state.dispatch(StarAction::PromoteCard(id));
+                    None
+                },
+                CardMove::Demote(id) => {
+                    state.dispatch(StarAction::DemoteCard(id));
+                    None
+                },
+            };
+            if let Some((id, (lane, position))) = target {
+                state.dispatch(StarAction::MoveCard { id, lane, position });
+            }
+        })
+    };
+    let drop_hint = |id:CardId| match *drag {
+        Some((_, Some(target @ DropTarget::Card { id: target_id, .. }))) if target_id == id => Some(target),
+        _ => None,
+    };
+    let dragging = drag.is_some();
+
     let cards = state.cards.clone();
     let p_cards = state.priority_cards.clone();
     html! {
@@ -44,27 +183,102 @@ fn Star() -> Html {
             <h1 class="title">{ "Star" }<span class="material-symbols-outlined star">{ "star" }</span></h1>
             <p class="subtitle">{ "Barista Helper" }</p>
             <div class="card_column">
-            {
-                for p_cards.iter().enumerate().map(|(index, card)| {
-                    html! {
-                        <Card card_type={ card.card_type } is_priority={true} index={index} create_card={create_card.clone()} add_priority_card={set_priority_card.clone()} destroy_priority={destroy_priority.clone()}  destroy_card={destroy_card.clone()} />
-                    }
-                })
+            if dragging && p_cards.is_empty() {
+                <div class="card_drop_zone" data-drop-lane="priority">{ "Drop here to make it a priority" }</div>
             }
             {
-                for cards.iter().enumerate().map(|(index, card)| {
+                // One keyed list for both lanes, so moving a card between them keeps its element and state.
+                for p_cards.iter().map(|card| (true, card)).chain(cards.iter().map(|card| (false, card))).map(|(is_priority, card)| {
                     html! {
-                        <Card card_type={ card.card_type } index={index} create_card={create_card.clone()} add_priority_card={set_priority_card.clone()} destroy_priority={destroy_priority.clone()}  destroy_card={destroy_card.clone()} />
+                        <Card key={ card.id.to_string() } card_type={ card.card_type.clone() } is_priority={is_priority} id={ card.id } label={ card.label.clone() } rename_card={rename_card.clone()} create_card={create_card.clone()} add_priority_card={set_priority_card.clone()} destroy_priority={destroy_priority.clone()}  destroy_card={destroy_card.clone()} move_card={move_card.clone()} drop_hint={drop_hint(card.id)} />
                     }
                 })
             }
+            if dragging && cards.is_empty() {
+                <div class="card_drop_zone" data-drop-lane="normal">{ "Drop here" }</div>
+            }
             </div>
         </div>
+        <div class="toast_host">
+        if let Some(undo_hint) = &state.undo_hint {
+            <div class="toast">
+                <p>{ undo_hint.clone() }</p>
+                <button class="button" onclick={undo}><span class="material-symbols-outlined icon">{ "undo" }</span>{ " Undo" }</button>
+            </div>
+        }
+        if !save_failures.failures.is_empty() {
+            <div class="toast">
+                <span class="material-symbols-outlined icon">{ "error" }</span>
+                <p>
+                    <b>{ "Your changes aren't being saved." }</b><br/>
+                    {
+                        save_failures.failures.iter().map(|failure| failure.to_string()).collect::<Vec<String>>().join(", ")
+                    }
+                </p>
+                <button class="button" onclick={retry_saves}>{ "Retry" }</button>
+                <a class="card_close_button" onclick={dismiss_save_failures}><span class="material-symbols-outlined">{ "close" }</span></a>
+            </div>
+        }
+        if let Some(err) = &*storage_error {
+            <div class="toast">
+                <span class="material-symbols-outlined icon">{ "warning" }</span>
+                <p>
+                    <b>{ "Saving to this browser's smaller backup storage." }</b><br/>
+                    { format!("The main storage couldn't be opened ({}). Everything is moved back once it works again.", err) }
+                </p>
+                <a class="card_close_button" onclick={dismiss_storage_error}><span class="material-symbols-outlined">{ "close" }</span></a>
+            </div>
+        }
+        if !save_failures.unreadable.is_empty() {
+            <div class="toast">
+                <span class="material-symbols-outlined icon">{ "error" }</span>
+                <p>
+                    <b>{ "Some saved data couldn't be read and was set aside." }</b><br/>
+                    {
+                        save_failures.unreadable.iter().map(|unreadable| unreadable.to_string()).collect::<Vec<String>>().join(", ")
+                    }
+                </p>
+                <a class="card_close_button" onclick={dismiss_unreadable}><span class="material-symbols-outlined">{ "close" }</span></a>
+            </div>
+        }
+        </div>
         </>
     }
 }
 
 fn main() {
     wasm_logger::init(wasm_logger::Config::default());
-    yew::Renderer::<Star>::new().render();
+    IndexedDbBackend::open(|result| {
+        let storage_error = match result {
+            Ok(backend) => {
+                // LocalStorage has the newest values on the first run on IndexedDB and after a session that fell back to it.
+                if backend.keys().is_empty() || LocalBackend.get(storage::FALLBACK_KEY).is_some() {
+                    let keys: Vec<String> = LocalBackend.keys().into_iter().filter(|key| key != storage::FALLBACK_KEY).collect();
+                    for key in backend.keys() {
+                        if !keys.contains(&key) {
+                            backend.remove(&key);
+                        }
+                    }
+                    for key in keys {
+                        if let Some(value) = LocalBackend.get(&key) {
+                            if let Err(err) = backend.set(&key, &value) {
+                                log::error!("{}", err);
+                            }
+                        }
+                    }
+                    LocalBackend.remove(storage::FALLBACK_KEY);
+                }
+                storage::set_backend(Rc::new(backend));
+                None
+            },
+            Err(err) => {
+                log::error!("IndexedDB unavailable, using LocalStorage: {}", err);
+                if let Err(err) = LocalBackend.set(storage::FALLBACK_KEY, "true") {
+                    log::error!("{}", err);
+                }
+                Some(err)
+            },
+        };
+        yew::Renderer::<Star>::with_props(StarProps { storage_error }).render();
+    });
 }
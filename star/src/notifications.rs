@@ -0,0 +1,68 @@
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{window, HtmlElement, Notification, NotificationOptions, NotificationPermission};
+use yew::Callback;
+
+use crate::components::CardId;
+
+// Browsers without the Notifications API don't define the global at all.
+pub fn supported() -> bool {
+    window().is_some_and(|window| js_sys::Reflect::has(&window, &JsValue::from_str("Notification")).unwrap_or(false))
+}
+
+pub fn permission() -> Option<NotificationPermission> {
+    supported().then(Notification::permission)
+}
+
+// Has to be called from a click or the browser ignores the request.
+pub fn request_permission(on_answer: Callback<NotificationPermission>) {
+    if !supported() {
+        return;
+    }
+    match Notification::request_permission() {
+        Ok(promise) => {
+            let answered = Closure::once(move |_: JsValue| on_answer.emit(Notification::permission()));
+            let _ = promise.then(&answered);
+            answered.forget();
+        },
+        Err(err) => log::error!("Could not ask for notification permission: {:?}", err),
+    }
+}
+
+// Posts a system notification if they're allowed. A newer notification with the same tag replaces the old one
+// instead of stacking up. Clicking it brings Star to the front and focuses the card.
+pub fn post(title: &str, body: &str, tag: &str, card: Option<CardId>) {
+    if permission() != Some(NotificationPermission::Granted) {
+        return;
+    }
+    // Plain object rather than the NotificationOptions builder, whose methods differ between web-sys versions.
+    let options = js_sys::Object::new();
+    let _ = js_sys::Reflect::set(&options, &"body".into(), &body.into());
+    let _ = js_sys::Reflect::set(&options, &"tag".into(), &tag.into());
+    let notification = match Notification::new_with_options(title, options.unchecked_ref::<NotificationOptions>()) {
+        Ok(notification) => notification,
+        Err(err) => return log::error!("Could not post notification: {:?}", err),
+    };
+    let clicked = notification.clone();
+    let on_click = Closure::once_into_js(move || {
+        clicked.close();
+        if let Some(window) = window() {
+            let _ = window.focus();
+        }
+        if let Some(card) = card {
+            focus_card(card);
+        }
+    });
+    notification.set_onclick(Some(on_click.unchecked_ref()));
+}
+
+pub fn focus_card(card: CardId) {
+    let element = gloo::utils::document()
+        .query_selector(&format!("[data-card-id=\"{}\"]", card))
+        .ok()
+        .flatten()
+        .and_then(|element| element.dyn_into::<HtmlElement>().ok());
+    if let Some(element) = element {
+        element.scroll_into_view();
+        let _ = element.focus();
+    }
+}
@@ -0,0 +1,595 @@
+use std::fmt::Write;
+
+use chrono::{Date, DateTime, Duration, Local};
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::{files::use_files, storage::{MigrationError, Schema, SaveError, StorableData, StorageBackend, Versioned}};
+
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum Station {
+    HotBar,
+    ColdBar,
+}
+
+impl Station {
+    pub const ALL: [Station; 2] = [Station::HotBar, Station::ColdBar];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Station::HotBar => "Hot Bar",
+            Station::ColdBar => "Cold Bar",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Station::HotBar => "coffee",
+            Station::ColdBar => "blender",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Station> {
+        Station::ALL.iter().copied().find(|station| station.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+// How long a product is good for once it's prepped.
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum ExpiryRule {
+    Hours(u32),
+    Days(u32),
+    // Tossed when the store closes.
+    EndOfDay,
+    // Pulled from the freezer, ready once thawed and good for a number of days from then.
+    Thaw { thaw_hours: u32, days: u32 },
+}
+
+impl ExpiryRule {
+    pub const KINDS: [&'static str; 4] = ["Hours", "Days", "End of day", "Thaw"];
+
+    pub fn kind(self) -> &'static str {
+        match self {
+            ExpiryRule::Hours(_) => "Hours",
+            ExpiryRule::Days(_) => "Days",
+            ExpiryRule::EndOfDay => "End of day",
+            ExpiryRule::Thaw { .. } => "Thaw",
+        }
+    }
+
+    fn of_kind(kind: &str) -> Option<ExpiryRule> {
+        match kind {
+            "Hours" => Some(ExpiryRule::Hours(24)),
+            "Days" => Some(ExpiryRule::Days(1)),
+            "End of day" => Some(ExpiryRule::EndOfDay),
+            "Thaw" => Some(ExpiryRule::Thaw { thaw_hours: 12, days: 2 }),
+            _ => None,
+        }
+    }
+
+    // The dates to mark on something prepped at the given time, labelled for products that carry more than one.
+    pub fn dates(self, prepped: DateTime<Local>, close_hour: u32) -> Vec<(&'static str, DateTime<Local>)> {
+        match self {
+            ExpiryRule::Hours(hours) => vec![("Use by", prepped + Duration::hours(hours.into()))],
+            ExpiryRule::Days(days) => vec![("Use by", prepped + Duration::days(days.into()))],
+            ExpiryRule::EndOfDay => {
+                let close = |day: Date<Local>| day.and_hms_opt(close_hour.min(23), 0, 0);
+                // Prepped after closing counts towards the next business day.
+                let at = close(prepped.date()).filter(|close| *close > prepped).or_else(|| close(prepped.date().succ()));
+                at.map(|at| vec![("Use by", at)]).unwrap_or_default()
+            },
+            ExpiryRule::Thaw { thaw_hours, days } => {
+                let thawed = prepped + Duration::hours(thaw_hours.into());
+                vec![("Thawed", thawed), ("Use by", thawed + Duration::days(days.into()))]
+            },
+        }
+    }
+
+    // Short form used in CSV files, like 24h, 3d, close or thaw 12h 2d.
+    fn code(self) -> String {
+        match self {
+            ExpiryRule::Hours(hours) => format!("{}h", hours),
+            ExpiryRule::Days(days) => format!("{}d", days),
+            ExpiryRule::EndOfDay => "close".to_string(),
+            ExpiryRule::Thaw { thaw_hours, days } => format!("thaw {}h {}d", thaw_hours, days),
+        }
+    }
+
+    fn from_code(code: &str) -> Option<ExpiryRule> {
+        let number = |part: &str, unit: char| part.strip_suffix(unit).and_then(|number| number.trim().parse().ok());
+        let parts: Vec<&str> = code.split_whitespace().collect();
+        match parts[..] {
+            [close] if close.eq_ignore_ascii_case("close") => Some(ExpiryRule::EndOfDay),
+            [amount] => number(amount, 'h').map(ExpiryRule::Hours).or_else(|| number(amount, 'd').map(ExpiryRule::Days)),
+            [thaw, thaw_hours, days] if thaw.eq_ignore_ascii_case("thaw") => Some(ExpiryRule::Thaw {
+                thaw_hours: number(thaw_hours, 'h')?,
+                days: number(days, 'd')?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct DaydotProduct {
+    pub name: String,
+    pub station: Station,
+    pub expiry: ExpiryRule,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ProductCatalog {
+    pub products: Vec<DaydotProduct>,
+    // Hour the business day ends, for products used by close.
+    #[serde(default = "default_close_hour")]
+    pub close_hour: u32,
+}
+
+fn default_close_hour() -> u32 {
+    21
+}
+
+// Shipped with the app and used until the store edits or imports its own.
+const BUNDLED_PRODUCTS: &str = include_str!("daydot_products.json");
+
+impl Default for ProductCatalog {
+    fn default() -> Self {
+        DAYDOT_PRODUCTS.upgrade(BUNDLED_PRODUCTS).expect("The bundled Daydot products are invalid.")
+    }
+}
+
+pub const DAYDOT_PRODUCTS: Schema<ProductCatalog> = Schema::new("daydot_products", "Daydot products", &[products_expiry_rules]);
+
+// Version 0 -> 1: a product's shelf life in days becomes one of several expiry rules.
+fn products_expiry_rules(mut data: Value) -> Result<Value, String> {
+    let products = data.get_mut("products").and_then(Value::as_array_mut).ok_or("Daydot products has no products")?;
+    for product in products {
+        let product = product.as_object_mut().ok_or("Daydot product is not an object")?;
+        let days = product.remove("shelf_life_days").and_then(|days| days.as_u64()).ok_or("Daydot product has no shelf life")?;
+        product.insert("expiry".to_string(), serde_json::json!({ "Days": days }));
+    }
+    Ok(data)
+}
+
+impl StorableData for ProductCatalog {
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self {
+        DAYDOT_PRODUCTS.load(backend).unwrap_or_default()
+    }
+
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        DAYDOT_PRODUCTS.save(backend, self)
+    }
+}
+
+#[derive(Debug)]
+pub enum ProductImportError {
+    Json(MigrationError),
+    MissingHeader,
+    Row { line: usize, reason: String },
+    Empty,
+}
+
+impl std::fmt::Display for ProductImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProductImportError::Json(err) => write!(f, "{}", err),
+            ProductImportError::MissingHeader => write!(f, "the first line has to be the header \"{}\"", CSV_HEADER),
+            ProductImportError::Row { line, reason } => write!(f, "line {}: {}", line, reason),
+            ProductImportError::Empty => write!(f, "there are no products in the file"),
+        }
+    }
+}
+
+const CSV_HEADER: &str = "name,station,expiry";
+// Files exported before expiry rules have the shelf life in days instead.
+const CSV_HEADER_DAYS: &str = "name,station,shelf_life_days";
+
+impl ProductCatalog {
+    pub fn at(&self, station: Station) -> impl Iterator<Item = &DaydotProduct> {
+        self.products.iter().filter(move |product| product.station == station)
+    }
+
+    // Exported with its schema version, so files from older builds can still be imported.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(&Versioned { version: DAYDOT_PRODUCTS.version(), data: self })
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_HEADER) + "\n";
+        for product in &self.products {
+            let _ = writeln!(csv, "{},{},{}", csv_field(&product.name), product.station.name(), product.expiry.code());
+        }
+        csv
+    }
+
+    // Takes either of the export formats, going by whether it looks like JSON. CSV files only list the products,
+    // so the catalog keeps the close hour it already had.
+    pub fn parse(text: &str, close_hour: u32) -> Result<ProductCatalog, ProductImportError> {
+        let catalog = if text.trim_start().starts_with('{') {
+            DAYDOT_PRODUCTS.upgrade(text).map_err(ProductImportError::Json)?
+        } else {
+            ProductCatalog { products: Self::parse_csv(text)?, close_hour }
+        };
+        if catalog.products.is_empty() {
+            return Err(ProductImportError::Empty);
+        }
+        Ok(catalog)
+    }
+
+    fn parse_csv(text: &str) -> Result<Vec<DaydotProduct>, ProductImportError> {
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let in_days = match lines.next() {
+            Some((_, header)) if header.trim().eq_ignore_ascii_case(CSV_HEADER) => false,
+            Some((_, header)) if header.trim().eq_ignore_ascii_case(CSV_HEADER_DAYS) => true,
+            _ => return Err(ProductImportError::MissingHeader),
+        };
+        lines.map(|(index, line)| {
+            let row_error = |reason: String| ProductImportError::Row { line: index + 1, reason };
+            let fields = split_csv_line(line).map_err(|reason| row_error(reason.to_string()))?;
+            let [name, station, expiry] = <[String; 3]>::try_from(fields).map_err(|fields| row_error(format!("expected 3 fields, found {}", fields.len())))?;
+            if name.trim().is_empty() {
+                return Err(row_error("the name is empty".to_string()));
+            }
+            Ok(DaydotProduct {
+                name: name.trim().to_string(),
+                station: Station::from_name(&station).ok_or_else(|| row_error(format!("\"{}\" is not a station, use Hot Bar or Cold Bar", station)))?,
+                expiry: match in_days {
+                    true => expiry.trim().parse().map(ExpiryRule::Days).map_err(|_| row_error(format!("\"{}\" is not a number of days", expiry)))?,
+                    false => ExpiryRule::from_code(&expiry).ok_or_else(|| row_error(format!("\"{}\" is not an expiry, use something like 24h, 3d, close or thaw 12h 2d", expiry)))?,
+                },
+            })
+        }).collect()
+    }
+}
+
+// Quotes a field when it holds anything CSV would otherwise split on.
+fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn split_csv_line(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("There is always a field.");
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            },
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("a quoted field is never closed");
+    }
+    Ok(fields)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ProductCatalogEditorData {
+    pub catalog: ProductCatalog,
+    pub on_change: Callback<ProductCatalog>,
+}
+
+#[function_component]
+pub fn ProductCatalogEditor(data: &ProductCatalogEditorData) -> Html {
+    let files = use_files();
+    let message = use_state(|| None::<String>);
+    let file_input_ref = use_node_ref();
+    let name_input_ref = use_node_ref();
+
+    let set_catalog = {
+        let on_change = data.on_change.clone();
+        Callback::from(move |catalog: ProductCatalog| {
+            catalog.persist();
+            on_change.emit(catalog);
+        })
+    };
+
+    let change = |index: usize, apply: fn(&mut DaydotProduct, &str)| {
+        let catalog = data.catalog.clone();
+        let set_catalog = set_catalog.clone();
+        Callback::from(move |e: Event| {
+            let value = match e.target_dyn_into::<HtmlSelectElement>() {
+                Some(select) => select.value(),
+                None => e.target_unchecked_into::<HtmlInputElement>().value(),
+            };
+            let mut catalog = catalog.clone();
+            apply(&mut catalog.products[index], &value);
+            set_catalog.emit(catalog);
+        })
+    };
+    let remove = |index: usize| {
+        let catalog = data.catalog.clone();
+        let set_catalog = set_catalog.clone();
+        Callback::from(move |_| {
+            let mut catalog = catalog.clone();
+            catalog.products.remove(index);
+            set_catalog.emit(catalog);
+        })
+    };
+    let add_product = {
+        let catalog = data.catalog.clone();
+        let set_catalog = set_catalog.clone();
+        let name_input_ref = name_input_ref.clone();
+        Callback::from(move |_| {
+            let input = name_input_ref.cast::<HtmlInputElement>().expect("Product name input not initialized correctly.");
+            let name = input.value().trim().to_string();
+            if name.is_empty() {
+                return;
+            }
+            let mut catalog = catalog.clone();
+            catalog.products.push(DaydotProduct { name, station: Station::HotBar, expiry: ExpiryRule::Days(1) });
+            input.set_value("");
+            set_catalog.emit(catalog);
+        })
+    };
+    let close_hour_changed = {
+        let catalog = data.catalog.clone();
+        let set_catalog = set_catalog.clone();
+        Callback::from(move |e: Event| {
+            if let Ok(hour) = e.target_unchecked_into::<HtmlInputElement>().value().parse::<u32>() {
+                set_catalog.emit(ProductCatalog { close_hour: hour.min(23), ..catalog.clone() });
+            }
+        })
+    };
+    let reset = {
+        let set_catalog = set_catalog.clone();
+        let message = message.clone();
+        Callback::from(move |_| {
+            set_catalog.emit(ProductCatalog::default());
+            message.set(Some("Back to the bundled products.".to_string()));
+        })
+    };
+
+    let export = |csv: bool| {
+        let catalog = data.catalog.clone();
+        let files = files.clone();
+        let message = message.clone();
+        Callback::from(move |_| {
+            let contents = if csv { Ok(catalog.to_csv()) } else { catalog.to_json() };
+            match contents {
+                Ok(contents) => files.download(
+                    if csv { "daydot-products.csv" } else { "daydot-products.json" },
+                    if csv { "text/csv" } else { "application/json" },
+                    &contents,
+                ),
+                Err(err) => message.set(Some(format!("Could not export products: {}", err))),
+            }
+        })
+    };
+
+    let file_chosen = {
+        let file_input_ref = file_input_ref.clone();
+        let files = files.clone();
+        let set_catalog = set_catalog.clone();
+        let message = message.clone();
+        let close_hour = data.catalog.close_hour;
+        Callback::from(move |_: Event| {
+            let input = file_input_ref.cast::<HtmlInputElement>().expect("File input not found.");
+            let set_catalog = set_catalog.clone();
+            let message = message.clone();
+            files.read_chosen(&input, move |result| {
+                match result.and_then(|text| ProductCatalog::parse(&text, close_hour).map_err(|err| err.to_string())) {
+                    Ok(catalog) => {
+                        message.set(Some(format!("Imported {} products.", catalog.products.len())));
+                        set_catalog.emit(catalog);
+                    },
+                    Err(err) => message.set(Some(format!("This file can't be imported: {}", err))),
+                }
+            });
+        })
+    };
+
+    html! {
+        <div class="product_catalog">
+            <table>
+                <tr><th>{ "Product" }</th><th>{ "Station" }</th><th>{ "Expires" }</th><th></th></tr>
+                {
+                    for data.catalog.products.iter().enumerate().map(|(index, product)| html! {
+                        <tr>
+                            <td><input class="text_input" type="text" value={ product.name.clone() } onchange={ change(index, |product, value| {
+                                if !value.trim().is_empty() {
+                                    product.name = value.trim().to_string();
+                                }
+                            }) } /></td>
+                            <td>
+                                <select onchange={ change(index, |product, value| {
+                                    if let Some(station) = Station::from_name(value) {
+                                        product.station = station;
+                                    }
+                                }) }>
+                                {
+                                    for Station::ALL.iter().map(|station| html! {
+                                        <option value={ station.name() } selected={ *station == product.station }>{ station.name() }</option>
+                                    })
+                                }
+                                </select>
+                            </td>
+                            <td>
+                                <select onchange={ change(index, |product, value| {
+                                    if let Some(expiry) = ExpiryRule::of_kind(value) {
+                                        product.expiry = expiry;
+                                    }
+                                }) }>
+                                {
+                                    for ExpiryRule::KINDS.iter().map(|kind| html! {
+                                        <option value={ *kind } selected={ *kind == product.expiry.kind() }>{ *kind }</option>
+                                    })
+                                }
+                                </select>
+                                {
+                                    match product.expiry {
+                                        ExpiryRule::Hours(hours) => html! {
+                                            <input class="number_input" type="number" min="1" value={ hours.to_string() } onchange={ change(index, |product, value| {
+                                                if let Ok(hours) = value.parse() {
+                                                    product.expiry = ExpiryRule::Hours(hours);
+                                                }
+                                            }) } />
+                                        },
+                                        ExpiryRule::Days(days) => html! {
+                                            <input class="number_input" type="number" min="0" value={ days.to_string() } onchange={ change(index, |product, value| {
+                                                if let Ok(days) = value.parse() {
+                                                    product.expiry = ExpiryRule::Days(days);
+                                                }
+                                            }) } />
+                                        },
+                                        ExpiryRule::EndOfDay => html! {},
+                                        ExpiryRule::Thaw { thaw_hours, days } => html! {
+                                            <>
+                                                <input class="number_input" type="number" min="0" title="Hours to thaw" value={ thaw_hours.to_string() } onchange={ change(index, |product, value| {
+                                                    if let (Ok(thaw_hours), ExpiryRule::Thaw { days, .. }) = (value.parse(), product.expiry) {
+                                                        product.expiry = ExpiryRule::Thaw { thaw_hours, days };
+                                                    }
+                                                }) } />{ "h, then " }
+                                                <input class="number_input" type="number" min="0" title="Days once thawed" value={ days.to_string() } onchange={ change(index, |product, value| {
+                                                    if let (Ok(days), ExpiryRule::Thaw { thaw_hours, .. }) = (value.parse(), product.expiry) {
+                                                        product.expiry = ExpiryRule::Thaw { thaw_hours, days };
+                                                    }
+                                                }) } />{ "d" }
+                                            </>
+                                        },
+                                    }
+                                }
+                            </td>
+                            <td>
+                                <button class="button outlined" title="Remove" onclick={ remove(index) }>
+                                    <span class="material-symbols-outlined">{ "delete" }</span>
+                                </button>
+                            </td>
+                        </tr>
+                    })
+                }
+            </table>
+            <label>{ "The business day ends at " }
+                <input class="number_input" type="number" min="0" max="23" value={ data.catalog.close_hour.to_string() } onchange={ close_hour_changed } />{ ":00" }
+            </label>
+            <input ref={ name_input_ref } class="text_input" size="1" type="text" placeholder="New product, e.g. Pumpkin Sauce" />
+            <button class="button" onclick={ add_product }><span class="material-symbols-outlined icon">{ "add" }</span>{ " Add Product" }</button>
+            <hr/>
+            <button class="button outlined" onclick={ export(false) }><span class="material-symbols-outlined">{ "download" }</span>{ " JSON" }</button>
+            { " " }
+            <button class="button outlined" onclick={ export(true) }><span class="material-symbols-outlined">{ "download" }</span>{ " CSV" }</button>
+            { " " }
+            <button class="button outlined" onclick={ reset }>{ "Reset to bundled list" }</button>
+            <p><b>{ "Import products" }</b><br/><small>{ "Replaces the whole list. CSV files need the header " }<code>{ CSV_HEADER }</code></small></p>
+            <input ref={ file_input_ref } onchange={ file_chosen } type="file" accept="application/json,.json,text/csv,.csv" />
+            if let Some(message) = &*message {
+                <p>{ message.clone() }</p>
+            }
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::clock;
+
+    fn local(day: u32, hour: u32) -> DateTime<Local> {
+        Local.ymd(2024, 3, day).and_hms(hour, 0, 0)
+    }
+
+    #[test]
+    fn hours_and_days_count_from_prep() {
+        clock::fake_local(2024, 3, 5, 9, 0);
+        assert_eq!(ExpiryRule::Hours(4).dates(clock::local_now(), 21), vec![("Use by", local(5, 13))]);
+        assert_eq!(ExpiryRule::Days(3).dates(clock::local_now(), 21), vec![("Use by", local(8, 9))]);
+    }
+
+    #[test]
+    fn end_of_day_is_the_next_close() {
+        let clock = clock::fake_local(2024, 3, 5, 9, 0);
+        assert_eq!(ExpiryRule::EndOfDay.dates(clock::local_now(), 21), vec![("Use by", local(5, 21))]);
+        // Prepped after closing counts towards the next business day.
+        clock.advance(Duration::hours(13));
+        assert_eq!(ExpiryRule::EndOfDay.dates(clock::local_now(), 21), vec![("Use by", local(6, 21))]);
+    }
+
+    #[test]
+    fn thawed_products_are_good_from_when_they_thaw() {
+        clock::fake_local(2024, 3, 5, 20, 0);
+        let dates = ExpiryRule::Thaw { thaw_hours: 12, days: 2 }.dates(clock::local_now(), 21);
+        assert_eq!(dates, vec![("Thawed", local(6, 8)), ("Use by", local(8, 8))]);
+    }
+
+    #[test]
+    fn bundled_products_parse() {
+        let catalog = ProductCatalog::default();
+        assert!(!catalog.products.is_empty());
+        assert!(Station::ALL.iter().all(|station| catalog.at(*station).next().is_some()));
+    }
+
+    #[test]
+    fn csv_fields_can_be_quoted() {
+        assert_eq!(split_csv_line("Milk,Hot Bar,3d").unwrap(), vec!["Milk", "Hot Bar", "3d"]);
+        assert_eq!(split_csv_line(r#""Oat, barista",Hot Bar,3d"#).unwrap(), vec!["Oat, barista", "Hot Bar", "3d"]);
+        assert_eq!(split_csv_line(r#""The ""good"" milk",Hot Bar,3d"#).unwrap(), vec![r#"The "good" milk"#, "Hot Bar", "3d"]);
+        assert_eq!(split_csv_line("Milk,,"), Ok(vec!["Milk".to_string(), String::new(), String::new()]));
+        assert!(split_csv_line(r#""Milk,Hot Bar,3d"#).is_err());
+        assert_eq!(csv_field(r#"Oat, "barista""#), r#""Oat, ""barista""""#);
+    }
+
+    #[test]
+    fn csv_files_can_have_windows_line_endings() {
+        let catalog = ProductCatalog::parse("name,station,expiry\r\nMilk,Hot Bar,3d\r\nCold foam,Cold Bar,close\r\n", 21).unwrap();
+        assert_eq!(catalog.products, vec![
+            DaydotProduct { name: "Milk".to_string(), station: Station::HotBar, expiry: ExpiryRule::Days(3) },
+            DaydotProduct { name: "Cold foam".to_string(), station: Station::ColdBar, expiry: ExpiryRule::EndOfDay },
+        ]);
+    }
+
+    #[test]
+    fn csv_rows_are_checked() {
+        let row_error = |csv: &str| match ProductCatalog::parse(csv, 21) {
+            Err(ProductImportError::Row { line, .. }) => line,
+            other => panic!("expected a row error, got {:?}", other),
+        };
+        assert_eq!(row_error("name,station,expiry\nMilk,Hot Bar\n"), 2);
+        assert_eq!(row_error("name,station,expiry\nMilk,Hot Bar,3d\n\nMilk,Hot Bar,3d,extra\n"), 4);
+        assert_eq!(row_error("name,station,expiry\nMilk,Front Bar,3d\n"), 2);
+        assert_eq!(row_error("name,station,expiry\nMilk,Hot Bar,3 weeks\n"), 2);
+        assert_eq!(row_error("name,station,expiry\n ,Hot Bar,3d\n"), 2);
+        assert!(matches!(ProductCatalog::parse("Milk,Hot Bar,3d\n", 21), Err(ProductImportError::MissingHeader)));
+        assert!(matches!(ProductCatalog::parse("name,station,expiry\n", 21), Err(ProductImportError::Empty)));
+    }
+
+    #[test]
+    fn shelf_life_csv_files_are_read_as_days() {
+        let catalog = ProductCatalog::parse("name,station,shelf_life_days\nMilk,Hot Bar,3\n", 21).unwrap();
+        assert_eq!(catalog.products[0].expiry, ExpiryRule::Days(3));
+    }
+
+    #[test]
+    fn exports_import_back() {
+        let mut catalog = ProductCatalog { close_hour: 22, ..ProductCatalog::default() };
+        catalog.products.push(DaydotProduct { name: r#"Oat, "barista""#.to_string(), station: Station::ColdBar, expiry: ExpiryRule::Thaw { thaw_hours: 12, days: 2 } });
+        catalog.products.push(DaydotProduct { name: "Pastry".to_string(), station: Station::HotBar, expiry: ExpiryRule::Hours(4) });
+
+        assert_eq!(ProductCatalog::parse(&catalog.to_json().unwrap(), 21).unwrap(), catalog);
+        assert_eq!(ProductCatalog::parse(&catalog.to_csv(), catalog.close_hour).unwrap(), catalog);
+    }
+
+    #[test]
+    fn csv_imports_keep_the_close_hour() {
+        let catalog = ProductCatalog { close_hour: 22, ..ProductCatalog::default() };
+        assert_eq!(ProductCatalog::parse(&catalog.to_csv(), catalog.close_hour).unwrap().close_hour, 22);
+    }
+}
@@ -0,0 +1,238 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use web_sys::{HtmlInputElement, KeyboardEvent};
+use yew::prelude::*;
+
+use crate::{clock, storage::{Schema, SaveError, StorableData, StorageBackend}};
+
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[serde(transparent)]
+pub struct RoastId(pub u64);
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Roast {
+    pub id: RoastId,
+    pub name: String,
+    // CSS colour the roast is tagged with, so it can be told apart at a glance.
+    pub colour: String,
+    // Minutes brewed coffee of this roast can be held before it has to be tossed.
+    pub hold_minutes: u32,
+    // Inactive roasts are kept for the record but left out of the rotation.
+    pub active: bool,
+}
+
+impl Roast {
+    pub fn tag(&self) -> Html {
+        html! {
+            <span class={ classes!("roast_tag", (!self.active).then_some("roast_inactive")) }>
+                <span class="roast_colour" style={ format!("background-color: {}", self.colour) }></span>
+                { self.name.clone() }
+            </span>
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RoastCatalog {
+    pub roasts: Vec<Roast>,
+}
+
+// The roasts Star knew before the catalog, their ids are what stored names migrate to.
+const LEGACY_ROASTS: [(u64, &str, &str); 3] = [
+    (1, "Pike", "#8b5a2b"),
+    (2, "Blonde", "#e3b778"),
+    (3, "Dark", "#3b2314"),
+];
+
+const DEFAULT_HOLD_MINUTES: u32 = 30;
+
+impl Default for RoastCatalog {
+    fn default() -> Self {
+        RoastCatalog {
+            roasts: LEGACY_ROASTS.iter().map(|(id, name, colour)| Roast {
+                id: RoastId(*id),
+                name: name.to_string(),
+                colour: colour.to_string(),
+                hold_minutes: DEFAULT_HOLD_MINUTES,
+                active: true,
+            }).collect(),
+        }
+    }
+}
+
+pub const PIKE: RoastId = RoastId(1);
+pub const BLONDE: RoastId = RoastId(2);
+pub const DARK: RoastId = RoastId(3);
+
+// Turns the roast names stored before the catalog into the ids of the matching entries, dropping any it doesn't know.
+pub fn migrate_roast_names(names: Value) -> Value {
+    let ids = match names {
+        Value::Array(names) => names.iter()
+            .filter_map(Value::as_str)
+            .filter_map(|name| LEGACY_ROASTS.iter().find(|(_, legacy, _)| *legacy == name))
+            .map(|(id, _, _)| Value::from(*id))
+            .collect(),
+        _ => Vec::new(),
+    };
+    Value::Array(ids)
+}
+
+pub const ROAST_CATALOG: Schema<RoastCatalog> = Schema::new("roast_catalog", "Roast catalog", &[]);
+
+impl StorableData for RoastCatalog {
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self {
+        ROAST_CATALOG.load(backend).unwrap_or_default()
+    }
+
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        ROAST_CATALOG.save(backend, self)
+    }
+}
+
+impl RoastCatalog {
+    pub fn get(&self, id: RoastId) -> Option<&Roast> {
+        self.roasts.iter().find(|roast| roast.id == id)
+    }
+
+    pub fn is_active(&self, id: RoastId) -> bool {
+        self.get(id).is_some_and(|roast| roast.active)
+    }
+
+    // Roasts that were taken out of the catalog still show up in old brews.
+    pub fn name(&self, id: RoastId) -> String {
+        self.get(id).map_or_else(|| "Removed roast".to_string(), |roast| roast.name.clone())
+    }
+
+    pub fn tag(&self, id: RoastId) -> Html {
+        match self.get(id) {
+            Some(roast) => roast.tag(),
+            None => html! { <span class="roast_tag roast_inactive">{ self.name(id) }</span> },
+        }
+    }
+
+    fn update(&self, roast: Roast) -> Self {
+        let mut catalog = self.clone();
+        if let Some(existing) = catalog.roasts.iter_mut().find(|existing| existing.id == roast.id) {
+            *existing = roast;
+        }
+        catalog
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RoastCatalogEditorData {
+    pub catalog: RoastCatalog,
+    pub on_change: Callback<RoastCatalog>,
+}
+
+#[function_component]
+pub fn RoastCatalogEditor(data: &RoastCatalogEditorData) -> Html {
+    let name_input_ref = use_node_ref();
+    let name_error = use_state(|| None::<String>);
+
+    let set_catalog = {
+        let on_change = data.on_change.clone();
+        Callback::from(move |catalog: RoastCatalog| {
+            catalog.persist();
+            on_change.emit(catalog);
+        })
+    };
+
+    let add_roast = {
+        let catalog = data.catalog.clone();
+        let set_catalog = set_catalog.clone();
+        let name_input_ref = name_input_ref.clone();
+        let name_error = name_error.clone();
+        Callback::from(move |_| {
+            let input = name_input_ref.cast::<HtmlInputElement>().expect("Roast name input not initialized correctly.");
+            let name = input.value().trim().to_string();
+            if name.is_empty() {
+                return name_error.set(Some("Give the roast a name.".to_string()));
+            }
+            if catalog.roasts.iter().any(|roast| roast.name.eq_ignore_ascii_case(&name)) {
+                return name_error.set(Some(format!("{} is already in the catalog.", name)));
+            }
+            let mut catalog = catalog.clone();
+            catalog.roasts.push(Roast {
+                id: RoastId(clock::unique_id()),
+                name,
+                colour: "#00704a".to_string(),
+                hold_minutes: DEFAULT_HOLD_MINUTES,
+                active: true,
+            });
+            input.set_value("");
+            name_error.set(None);
+            set_catalog.emit(catalog);
+        })
+    };
+
+    let add_on_enter = {
+        let add_roast = add_roast.clone();
+        Callback::from(move |e: KeyboardEvent| {
+            if e.key() == "Enter" {
+                add_roast.emit(());
+            }
+        })
+    };
+
+    let change = |roast: &Roast, apply: fn(&mut Roast, &str)| {
+        let catalog = data.catalog.clone();
+        let set_catalog = set_catalog.clone();
+        let roast = roast.clone();
+        Callback::from(move |e: Event| {
+            let mut roast = roast.clone();
+            apply(&mut roast, &e.target_unchecked_into::<HtmlInputElement>().value());
+            set_catalog.emit(catalog.update(roast));
+        })
+    };
+    let toggle_active = |roast: &Roast| {
+        let catalog = data.catalog.clone();
+        let set_catalog = set_catalog.clone();
+        let roast = Roast { active: !roast.active, ..roast.clone() };
+        Callback::from(move |_| set_catalog.emit(catalog.update(roast.clone())))
+    };
+    let remove = |id: RoastId| {
+        let catalog = data.catalog.clone();
+        let set_catalog = set_catalog.clone();
+        Callback::from(move |_| {
+            let mut catalog = catalog.clone();
+            catalog.roasts.retain(|roast| roast.id != id);
+            set_catalog.emit(catalog);
+        })
+    };
+
+    html! {
+        <div class="roast_catalog">
+            {
+                for data.catalog.roasts.iter().map(|roast| html! {
+                    <div class="roast_row" key={ roast.id.0.to_string() }>
+                        <input type="color" title="Colour tag" value={ roast.colour.clone() } onchange={ change(roast, |roast, value| roast.colour = value.to_string()) } />
+                        <input class="text_input" type="text" value={ roast.name.clone() } onchange={ change(roast, |roast, value| {
+                            if !value.trim().is_empty() {
+                                roast.name = value.trim().to_string();
+                            }
+                        }) } />
+                        <label title="How long it can be held once brewed">
+                            <input class="number_input" type="number" min="1" value={ roast.hold_minutes.to_string() } onchange={ change(roast, |roast, value| {
+                                if let Ok(minutes) = value.parse::<u32>() {
+                                    roast.hold_minutes = minutes.max(1);
+                                }
+                            }) } />{ " min" }
+                        </label>
+                        <a class="clickable" title={ if roast.active { "In the rotation, click to retire" } else { "Retired, click to bring back" } } onclick={ toggle_active(roast) }>
+                            <span class="material-symbols-outlined">{ if roast.active { "toggle_on" } else { "toggle_off" } }</span>
+                        </a>
+                        <button class="button outlined" title="Remove" onclick={ remove(roast.id) }>
+                            <span class="material-symbols-outlined">{ "delete" }</span>
+                        </button>
+                    </div>
+                })
+            }
+            <input ref={ name_input_ref } onkeydown={ add_on_enter } class="text_input" size="1" type="text" placeholder="New roast, e.g. Decaf Pike" />
+            if let Some(error) = &*name_error {
+                <p class="input_error">{ error }</p>
+            }
+            <button class="button" onclick={ add_roast.reform(|_| ()) }><span class="material-symbols-outlined icon">{ "add" }</span>{ " Add Roast" }</button>
+        </div>
+    }
+}
@@ -0,0 +1,346 @@
+use chrono::{DateTime, Datelike, Local, Timelike, Weekday};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::{components::CoffeesToBrew, editor::{self, ListChange}, roasts::{self, RoastCatalog, RoastId}, storage::{Schema, SaveError, StorableData, StorageBackend}};
+
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum RuleKind {
+    // Every roast in the rule is brewed each cycle.
+    Always,
+    // One roast per cycle, taking turns in the order listed.
+    Sequence,
+}
+
+impl RuleKind {
+    pub const ALL: [RuleKind; 2] = [RuleKind::Always, RuleKind::Sequence];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RuleKind::Always => "Always brew",
+            RuleKind::Sequence => "Take turns",
+        }
+    }
+}
+
+const WEEKDAYS: [Weekday; 7] = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun];
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RotationRule {
+    pub name: String,
+    pub kind: RuleKind,
+    pub roasts: Vec<RoastId>,
+    // Days the rule applies on, every day when empty.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    // Local hours the rule applies between, from inclusive and until exclusive.
+    // A window with until before from runs overnight.
+    pub from_hour: u32,
+    pub until_hour: u32,
+}
+
+impl RotationRule {
+    pub fn applies_at(&self, at: DateTime<Local>) -> bool {
+        let hour = at.hour();
+        let in_window = if self.from_hour <= self.until_hour {
+            self.from_hour <= hour && hour < self.until_hour
+        } else {
+            hour >= self.from_hour || hour < self.until_hour
+        };
+        in_window && (self.days.is_empty() || self.days.contains(&at.weekday()))
+    }
+
+    // What this rule adds to the next brew, given what was brewed last. Retired roasts are skipped.
+    fn next(&self, catalog: &RoastCatalog, last: &CoffeesToBrew) -> Vec<RoastId> {
+        match self.kind {
+            RuleKind::Always => self.roasts.iter().copied().filter(|roast| catalog.is_active(*roast)).collect(),
+            RuleKind::Sequence => {
+                // Carries on after the last roast of the sequence that was brewed, or starts over.
+                let after = self.roasts.iter().rposition(|roast| last.0.contains(roast)).map_or(0, |position| position + 1);
+                self.roasts.iter().cycle().skip(after).take(self.roasts.len()).copied().find(|roast| catalog.is_active(*roast)).into_iter().collect()
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RotationRules {
+    pub rules: Vec<RotationRule>,
+}
+
+impl Default for RotationRules {
+    // Pike all day, with Blonde and Dark taking turns in the morning.
+    fn default() -> Self {
+        RotationRules {
+            rules: vec![
+                RotationRule {
+                    name: "Pike all day".to_string(),
+                    kind: RuleKind::Always,
+                    roasts: vec![roasts::PIKE],
+                    days: Vec::new(),
+                    from_hour: 0,
+                    until_hour: 24,
+                },
+                RotationRule {
+                    name: "Morning rotation".to_string(),
+                    kind: RuleKind::Sequence,
+                    roasts: vec![roasts::BLONDE, roasts::DARK],
+                    days: Vec::new(),
+                    from_hour: 0,
+                    until_hour: 11,
+                },
+            ],
+        }
+    }
+}
+
+pub const BREW_ROTATION: Schema<RotationRules> = Schema::new("brew_rotation", "Brew rotation", &[rotation_roast_ids]);
+
+// Version 0 -> 1: roasts are catalog ids instead of names.
+fn rotation_roast_ids(mut data: Value) -> Result<Value, String> {
+    let rules = data.get_mut("rules").and_then(Value::as_array_mut).ok_or("Brew rotation has no rules")?;
+    for rule in rules {
+        let rule = rule.as_object_mut().ok_or("Rotation rule is not an object")?;
+        let roasts = rule.remove("roasts").unwrap_or_default();
+        rule.insert("roasts".to_string(), roasts::migrate_roast_names(roasts));
+    }
+    Ok(data)
+}
+
+impl StorableData for RotationRules {
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self {
+        BREW_ROTATION.load(backend).unwrap_or_default()
+    }
+
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        BREW_ROTATION.save(backend, self)
+    }
+}
+
+impl RotationRules {
+    // Every rule that applies at the time adds its roasts, in the order the rules are listed.
+    pub fn next_brew(&self, catalog: &RoastCatalog, last: &CoffeesToBrew, at: DateTime<Local>) -> CoffeesToBrew {
+        let mut brew = Vec::new();
+        for rule in self.rules.iter().filter(|rule| rule.applies_at(at)) {
+            for roast in rule.next(catalog, last) {
+                if !brew.contains(&roast) {
+                    brew.push(roast);
+                }
+            }
+        }
+        CoffeesToBrew(brew)
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RotationEditorData {
+    pub rules: RotationRules,
+    pub catalog: RoastCatalog,
+    pub on_change: Callback<RotationRules>,
+}
+
+#[function_component]
+pub fn RotationEditor(data: &RotationEditorData) -> Html {
+    let edit = |change: ListChange<RotationRule>| {
+        editor::save_edit(&data.rules, &data.on_change, move |rules: &mut RotationRules| change(&mut rules.rules))
+    };
+    let rule_changed = |index: usize| {
+        let rules = data.rules.clone();
+        let on_change = data.on_change.clone();
+        Callback::from(move |rule: RotationRule| {
+            let mut rules = rules.clone();
+            rules.rules[index] = rule;
+            rules.persist();
+            on_change.emit(rules);
+        })
+    };
+    let count = data.rules.rules.len();
+    html! {
+        <div class="rotation_editor">
+            {
+                for data.rules.rules.iter().enumerate().map(|(index, rule)| html! {
+                    <div class="rotation_rule">
+                        <RotationRuleEditor rule={ rule.clone() } catalog={ data.catalog.clone() } on_change={ rule_changed(index) } />
+                        { editor::row_buttons(index, count, edit) }
+                    </div>
+                })
+            }
+            if count == 0 {
+                <p>{ "No rules, nothing will be suggested to brew." }</p>
+            }
+            <button class="button" onclick={ edit(Box::new(|rules| rules.push(RotationRule {
+                name: format!("Rule {}", rules.len() + 1),
+                kind: RuleKind::Always,
+                roasts: Vec::new(),
+                days: Vec::new(),
+                from_hour: 0,
+                until_hour: 24,
+            }))) }><span class="material-symbols-outlined icon">{ "add" }</span>{ " Add Rule" }</button>
+            <button class="button outlined" onclick={ edit(Box::new(|rules| *rules = RotationRules::default().rules)) }>{ "Reset to default" }</button>
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct RotationRuleData {
+    rule: RotationRule,
+    catalog: RoastCatalog,
+    on_change: Callback<RotationRule>,
+}
+
+#[function_component]
+fn RotationRuleEditor(data: &RotationRuleData) -> Html {
+    let rule = &data.rule;
+    let change = |apply: fn(&mut RotationRule, &str)| {
+        let rule = rule.clone();
+        let on_change = data.on_change.clone();
+        Callback::from(move |e: Event| {
+            let value = match e.target_dyn_into::<HtmlSelectElement>() {
+                Some(select) => select.value(),
+                None => e.target_unchecked_into::<HtmlInputElement>().value(),
+            };
+            let mut rule = rule.clone();
+            apply(&mut rule, &value);
+            on_change.emit(rule);
+        })
+    };
+    let name_changed = change(|rule, value| {
+        if !value.trim().is_empty() {
+            rule.name = value.trim().to_string();
+        }
+    });
+    let kind_changed = change(|rule, value| {
+        if let Some(kind) = RuleKind::ALL.iter().find(|kind| kind.name() == value) {
+            rule.kind = *kind;
+        }
+    });
+    let from_changed = change(|rule, value| {
+        if let Ok(hour) = value.parse::<u32>() {
+            rule.from_hour = hour.min(23);
+        }
+    });
+    let until_changed = change(|rule, value| {
+        if let Ok(hour) = value.parse::<u32>() {
+            rule.until_hour = hour.min(24);
+        }
+    });
+    let add_roast = change(|rule, value| {
+        if let Ok(id) = value.parse::<u64>() {
+            rule.roasts.push(RoastId(id));
+        }
+    });
+    let toggle_day = |day: Weekday| {
+        let rule = rule.clone();
+        let on_change = data.on_change.clone();
+        Callback::from(move |_| {
+            let mut rule = rule.clone();
+            match rule.days.iter().position(|selected| *selected == day) {
+                Some(position) => { rule.days.remove(position); },
+                None => rule.days.push(day),
+            }
+            on_change.emit(rule);
+        })
+    };
+    let remove_roast = |index: usize| {
+        let rule = rule.clone();
+        let on_change = data.on_change.clone();
+        Callback::from(move |_| {
+            let mut rule = rule.clone();
+            rule.roasts.remove(index);
+            on_change.emit(rule);
+        })
+    };
+    html! {
+        <div class="rotation_rule_fields">
+            <input class="text_input" type="text" value={ rule.name.clone() } onchange={name_changed} />
+            <select onchange={kind_changed}>
+            {
+                for RuleKind::ALL.iter().map(|kind| html! {
+                    <option value={ kind.name() } selected={ *kind == rule.kind }>{ kind.name() }</option>
+                })
+            }
+            </select>
+            <p>
+            {
+                for rule.roasts.iter().enumerate().map(|(index, roast)| html! {
+                    <a class="rotation_roast clickable" title="Remove" onclick={ remove_roast(index) }>
+                        { data.catalog.tag(*roast) }<span class="material-symbols-outlined">{ "close" }</span>
+                    </a>
+                })
+            }
+                <select onchange={add_roast}>
+                    <option value="" selected=true>{ "Add a roast" }</option>
+                    {
+                        for data.catalog.roasts.iter().filter(|roast| roast.active).map(|roast| html! {
+                            <option value={ roast.id.0.to_string() }>{ roast.name.clone() }</option>
+                        })
+                    }
+                </select>
+            </p>
+            <label>{ "From " }
+                <input class="number_input" type="number" min="0" max="23" value={ rule.from_hour.to_string() } onchange={from_changed} />{ ":00" }
+            </label>
+            <label>{ " until " }
+                <input class="number_input" type="number" min="0" max="24" value={ rule.until_hour.to_string() } onchange={until_changed} />{ ":00" }
+            </label>
+            <p>
+            {
+                for WEEKDAYS.iter().map(|day| html! {
+                    <a class={ classes!("rotation_day", "clickable", rule.days.contains(day).then_some("selected")) } onclick={ toggle_day(*day) }>{ format!("{:?}", day) }</a>
+                })
+            }
+            if rule.days.is_empty() {
+                <small>{ " Every day" }</small>
+            }
+            </p>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock, roasts::{BLONDE, DARK, PIKE}};
+
+    #[test]
+    fn morning_brews_take_turns() {
+        clock::fake_local(2024, 3, 5, 7, 0);
+        let rules = RotationRules::default();
+        let catalog = RoastCatalog::default();
+
+        let first = rules.next_brew(&catalog, &CoffeesToBrew(Vec::new()), clock::local_now());
+        assert_eq!(first, CoffeesToBrew(vec![PIKE, BLONDE]));
+        let second = rules.next_brew(&catalog, &first, clock::local_now());
+        assert_eq!(second, CoffeesToBrew(vec![PIKE, DARK]));
+        let third = rules.next_brew(&catalog, &second, clock::local_now());
+        assert_eq!(third, CoffeesToBrew(vec![PIKE, BLONDE]));
+    }
+
+    #[test]
+    fn afternoon_brews_leave_out_morning_rules() {
+        clock::fake_local(2024, 3, 5, 14, 0);
+        let next = RotationRules::default().next_brew(&RoastCatalog::default(), &CoffeesToBrew(vec![PIKE, BLONDE]), clock::local_now());
+        assert_eq!(next, CoffeesToBrew(vec![PIKE]));
+    }
+
+    #[test]
+    fn retired_roasts_are_skipped() {
+        clock::fake_local(2024, 3, 5, 7, 0);
+        let mut catalog = RoastCatalog::default();
+        catalog.roasts.iter_mut().filter(|roast| roast.id == DARK).for_each(|roast| roast.active = false);
+        let next = RotationRules::default().next_brew(&catalog, &CoffeesToBrew(vec![PIKE, BLONDE]), clock::local_now());
+        assert_eq!(next, CoffeesToBrew(vec![PIKE, BLONDE]));
+    }
+
+    #[test]
+    fn rules_only_apply_on_their_days() {
+        // 2024-03-05 is a Tuesday.
+        clock::fake_local(2024, 3, 5, 7, 0);
+        let rule = RotationRule { days: vec![Weekday::Mon], ..RotationRules::default().rules[0].clone() };
+        assert!(!rule.applies_at(clock::local_now()));
+        let rule = RotationRule { days: vec![Weekday::Tue], ..rule };
+        assert!(rule.applies_at(clock::local_now()));
+    }
+}
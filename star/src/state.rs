@@ -1,12 +1,11 @@
-use std::{rc::Rc, collections::VecDeque, cell::Cell};
+use std::{cell::RefCell, rc::Rc, collections::{HashMap, VecDeque}, num::IntErrorKind};
 
-use chrono::{DateTime, Utc};
-use gloo::storage::{Storage, LocalStorage, errors::StorageError};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use serde::{Serialize, Deserialize};
-use storage::StorableData;
-use web_sys::{window, HtmlAudioElement};
+use serde_json::Value;
+use storage::{StorableData, StorageBackend, Schema, LoadError, SaveError, SaveEvent};
+use web_sys::window;
 use gloo::timers::callback::Interval;
-use wasm_timer::Instant;
 use yew::prelude::*;
 
 use crate::*;
@@ -16,44 +15,255 @@ use crate::*;
 pub enum StarAction {
     AddCard(CardType),
     AddPriorityCard(CardType),
-    DestroyPriorityCard(usize),
-    DestroyCard(usize),
+    DestroyPriorityCard(CardId),
+    DestroyCard(CardId),
+    RenameCard(CardId, Option<String>),
+    MoveCard { id:CardId, lane:Lane, position:usize },
+    PromoteCard(CardId),
+    DemoteCard(CardId),
+    Undo,
+    Redo,
+    DismissUndoHint,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Lane {
+    Priority,
+    Normal,
+}
+
+// Where a dragged card was let go.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DropTarget {
+    Card { id:CardId, after:bool },
+    // An empty lane's drop zone.
+    Lane(Lane),
 }
 
 #[derive(Properties, PartialEq)]
 pub struct StarData {
     pub priority_cards:VecDeque<CardData>,
     pub cards:VecDeque<CardData>,
+    pub history:History,
+    // Set after a destructive action, shown with an undo button.
+    pub undo_hint:Option<String>,
+}
+
+// The cards as they were around an action, for undo and redo.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct CardsSnapshot {
+    pub priority_cards:VecDeque<CardData>,
+    pub cards:VecDeque<CardData>,
+}
+
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct History {
+    pub undo:VecDeque<CardsSnapshot>,
+    pub redo:Vec<CardsSnapshot>,
+}
+
+const HISTORY_LIMIT: usize = 25;
+
+impl History {
+    fn push_undo(&mut self, snapshot:CardsSnapshot) {
+        self.undo.push_back(snapshot);
+        while self.undo.len() > HISTORY_LIMIT {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn record(&mut self, snapshot:CardsSnapshot) {
+        self.push_undo(snapshot);
+        self.redo.clear();
+    }
+}
+
+pub const CARDS: Schema<VecDeque<CardData>> = Schema::new("cards", "Cards", &[storage::wrap_unversioned, components::assign_card_ids]);
+pub const PRIORITY_CARDS: Schema<VecDeque<CardData>> = Schema::new("priority_cards", "Priority cards", &[storage::wrap_unversioned, components::assign_card_ids]);
+pub const HISTORY: Schema<History> = Schema::new("card_history", "Undo history", &[assign_history_card_ids]);
+
+// Version 0 -> 1: cards in the history get ids, like the stored card lists. The same card shows up in
+// many snapshots, so it gets one id across all of them, going by its lane, type and place among cards of that type.
+fn assign_history_card_ids(mut history: Value) -> Result<Value, String> {
+    let mut ids: HashMap<(&str, String, usize), Value> = HashMap::new();
+    for stack in ["undo", "redo"] {
+        for snapshot in history.get_mut(stack).and_then(Value::as_array_mut).ok_or("history is missing its stacks")? {
+            for lane in ["priority_cards", "cards"] {
+                let cards = snapshot.get_mut(lane).and_then(Value::as_array_mut).ok_or("snapshot is missing its cards")?;
+                let mut card_types: Vec<String> = Vec::new();
+                for card in cards {
+                    let card = card.as_object_mut().ok_or("card is not an object")?;
+                    let card_type = card.get("card_type").map(Value::to_string).unwrap_or_default();
+                    let nth = card_types.iter().filter(|seen| **seen == card_type).count();
+                    card_types.push(card_type.clone());
+                    card.remove("index");
+                    if !card.contains_key("id") {
+                        let id = ids.entry((lane, card_type, nth)).or_insert_with(|| Value::from(CardId::generate().0));
+                        card.insert("id".to_string(), id.clone());
+                    }
+                }
+            }
+        }
+    }
+    Ok(history)
 }
 
 impl StorableData for StarData {
-    fn load() -> Self {
-        let mut priority_cards:Option<VecDeque<CardData>> = None;
-        let mut cards:Option<VecDeque<CardData>> = None;
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self {
+        let data = StarData {
+            priority_cards: PRIORITY_CARDS.load(backend).unwrap_or_default(),
+            cards: CARDS.load(backend).unwrap_or_else(|| VecDeque::from([CardData::new(CardType::START_NEW_TASK)])),
+            history: HISTORY.load(backend).unwrap_or_default(),
+            undo_hint: None,
+        };
+        data.remove_orphaned_state(backend);
+        data
+    }
+
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        let cards = CARDS.save(backend, &self.cards);
+        let priority_cards = PRIORITY_CARDS.save(backend, &self.priority_cards);
+        let history = HISTORY.save(backend, &self.history);
+        cards.and(priority_cards).and(history)
+    }
+}
 
-        let cards_from_storage:Result<VecDeque<CardData>, gloo::storage::errors::StorageError> = LocalStorage::get("cards");
-        if let Ok(stored_cards) = cards_from_storage {
-            cards = Some(stored_cards)
+impl StarData {
+    fn snapshot(&self) -> CardsSnapshot {
+        CardsSnapshot {
+            priority_cards: self.priority_cards.clone(),
+            cards: self.cards.clone(),
         }
+    }
+
+    // Applies new cards, remembering the current ones so the change can be undone.
+    fn change(&self, priority_cards:VecDeque<CardData>, cards:VecDeque<CardData>, undo_hint:Option<String>) -> Rc<Self> {
+        let mut history = self.history.clone();
+        history.record(self.snapshot());
+        let data = StarData {
+            priority_cards,
+            cards,
+            history,
+            undo_hint,
+        };
+        data.persist();
+        data.into()
+    }
 
-        let priority_cards_from_storage:Result<VecDeque<CardData>, gloo::storage::errors::StorageError> = LocalStorage::get("priority_cards");
-        match priority_cards_from_storage {
-            Ok(stored_cards) => priority_cards = Some(stored_cards),
-            _ => {}
+    // A card of this type, or None when the type is single instance and either lane already has one.
+    fn new_card(&self, card_type:CardType) -> Option<CardData> {
+        let mut card = CardData::new(card_type.clone());
+        let existing = self.priority_cards.iter().chain(self.cards.iter()).filter(|card| card.card_type == card_type).count();
+        if card_type.allows_multiple() {
+            if existing > 0 {
+                card.label = Some(format!("{} {}", card_type.name(), existing + 1));
+            }
         }
-        
+        else if existing > 0 {
+            return None;
+        }
+        Some(card)
+    }
 
-        StarData { priority_cards: priority_cards.unwrap_or_default(), cards: cards.unwrap_or_else(|| VecDeque::from([CardData::new(CardType::StartNewTask)])) }
+    // Drops per-card state left behind by cards that are gone and can no longer be brought back with undo.
+    fn remove_orphaned_state<B: StorageBackend + ?Sized>(&self, backend: &B) {
+        let snapshots = std::iter::once(self.snapshot()).chain(self.history.undo.iter().cloned()).chain(self.history.redo.iter().cloned());
+        let known: Vec<String> = snapshots
+            .flat_map(|snapshot| snapshot.priority_cards.into_iter().chain(snapshot.cards))
+            .map(|card| card.id.to_string())
+            .collect();
+        let prefixes: Vec<String> = cards::REGISTRY.iter().filter_map(|kind| kind.persistence_key()).map(|key| key.to_owned() + ".").collect();
+        for key in backend.keys() {
+            let orphaned = prefixes.iter()
+                .filter_map(|prefix| key.strip_prefix(prefix.as_str()))
+                .any(|instance| instance.parse::<u64>().is_ok() && !known.iter().any(|id| id == instance));
+            if orphaned {
+                backend.remove(&key);
+            }
+        }
     }
 
-    fn save(self) -> Self {
-        if let Err(err) = LocalStorage::set("cards", self.cards.clone()) {
-            log::error!("{:?}", err);
+    fn lane(&self, lane:Lane) -> &VecDeque<CardData> {
+        match lane {
+            Lane::Priority => &self.priority_cards,
+            Lane::Normal => &self.cards,
         }
-        if let Err(err) = LocalStorage::set("priority_cards", self.priority_cards.clone()) {
-            log::error!("{:?}", err);
+    }
+
+    pub fn find(&self, id:CardId) -> Option<(Lane, usize)> {
+        [Lane::Priority, Lane::Normal].into_iter().find_map(|lane| {
+            self.lane(lane).iter().position(|card| card.id == id).map(|position| (lane, position))
+        })
+    }
+
+    // Cards that can't be closed, like the launcher, stay in their lane, as a priority card always has a close button.
+    fn can_move_to(&self, id:CardId, lane:Lane) -> bool {
+        match self.find(id) {
+            Some((from_lane, position)) => from_lane == lane || self.lane(from_lane)[position].card_type.closable(),
+            None => false,
         }
-        self
+    }
+
+    // Lane and position for MoveCard when a card is dropped on a target. Positions are counted
+    // with the moved card already taken out of its lane.
+    pub fn drop_position(&self, id:CardId, target:DropTarget) -> Option<(Lane, usize)> {
+        let (from_lane, from) = self.find(id)?;
+        let (lane, mut position) = match target {
+            DropTarget::Card { id: target, after } => {
+                if target == id {
+                    return None;
+                }
+                let (lane, position) = self.find(target)?;
+                (lane, position + after as usize)
+            },
+            DropTarget::Lane(lane) => (lane, self.lane(lane).len()),
+        };
+        if lane == from_lane && from < position {
+            position -= 1;
+        }
+        Some((lane, position)).filter(|(lane, _)| self.can_move_to(id, *lane))
+    }
+
+    // Moves a card one place up or down the column, crossing between the lanes at their boundary.
+    pub fn step_position(&self, id:CardId, up:bool) -> Option<(Lane, usize)> {
+        let (lane, position) = self.find(id)?;
+        match (lane, up) {
+            (_, true) if position > 0 => Some((lane, position - 1)),
+            (Lane::Normal, true) => Some((Lane::Priority, self.priority_cards.len())),
+            (_, false) if position + 1 < self.lane(lane).len() => Some((lane, position + 1)),
+            (Lane::Priority, false) => Some((Lane::Normal, 0)),
+            _ => None,
+        }.filter(|(lane, _)| self.can_move_to(id, *lane))
+    }
+
+    fn move_card(&self, id:CardId, lane:Lane, position:usize) -> Option<Rc<Self>> {
+        let (from_lane, from) = self.find(id)?;
+        if (from_lane, from) == (lane, position) || !self.can_move_to(id, lane) {
+            return None;
+        }
+        let mut priority_cards = self.priority_cards.clone();
+        let mut cards = self.cards.clone();
+        let card = match from_lane {
+            Lane::Priority => priority_cards.remove(from),
+            Lane::Normal => cards.remove(from),
+        }?;
+        let target = match lane {
+            Lane::Priority => &mut priority_cards,
+            Lane::Normal => &mut cards,
+        };
+        target.insert(position.min(target.len()), card);
+        Some(self.change(priority_cards, cards, None))
+    }
+
+    fn restore(&self, snapshot:CardsSnapshot, history:History) -> Rc<Self> {
+        let data = StarData {
+            priority_cards: snapshot.priority_cards,
+            cards: snapshot.cards,
+            history,
+            undo_hint: None,
+        };
+        data.persist();
+        data.into()
     }
 }
 
@@ -64,67 +274,143 @@ impl Reducible for StarData {
         match action {
             StarAction::AddCard(card_type) => {
                 let mut cards = self.cards.clone();
-                let mut has_card = false;
-                for card in &cards {
-                    if card.card_type == card_type {
-                        has_card = true;
-                        break;
-                    }
-                }
-                if !has_card {
-                    cards.push_back(CardData::new(card_type));
+                match self.new_card(card_type) {
+                    Some(card) => cards.push_back(card),
+                    None => return self,
                 }
-                StarData {
-                    priority_cards: self.priority_cards.clone(),
-                    cards,
-                }.save().into()
+                self.change(self.priority_cards.clone(), cards, None)
             },
             StarAction::AddPriorityCard(card_type) => {
                 let mut priority_cards = self.priority_cards.clone();
-                let mut has_card = false;
-                for card in &priority_cards {
-                    if card.card_type == card_type {
-                        has_card = true;
-                        break;
-                    }
-                }
-                if !has_card {
-                    priority_cards.push_front(CardData::new(card_type));
-                }
                 if let Some(window) = window() {
                     window.scroll_with_x_and_y(0.0, 0.0);
                 }
-                StarData {
-                    priority_cards,
-                    cards: self.cards.clone(),
-                }.save().into()
+                match self.new_card(card_type) {
+                    Some(card) => priority_cards.push_front(card),
+                    None => return self,
+                }
+                self.change(priority_cards, self.cards.clone(), None)
             },
-            StarAction::DestroyPriorityCard(index) => {
+            StarAction::RenameCard(id, label) => {
                 let mut priority_cards = self.priority_cards.clone();
-                if priority_cards.get(index).is_some() {
-                    priority_cards.remove(index);
+                let mut cards = self.cards.clone();
+                match priority_cards.iter_mut().chain(cards.iter_mut()).find(|card| card.id == id) {
+                    Some(card) if card.label != label => card.label = label,
+                    _ => return self,
                 }
-                StarData {
-                    priority_cards,
-                    cards: self.cards.clone(),
-                }.save().into()
+                self.change(priority_cards, cards, None)
             },
-            StarAction::DestroyCard(index) => {
+            StarAction::DestroyPriorityCard(id) => {
+                let mut priority_cards = self.priority_cards.clone();
+                priority_cards.retain(|card| card.id != id);
+                if priority_cards.len() == self.priority_cards.len() {
+                    return self;
+                }
+                self.change(priority_cards, self.cards.clone(), Some("Card closed".to_string()))
+            },
+            StarAction::DestroyCard(id) => {
                 let mut cards = self.cards.clone();
-                if cards.get(index).is_some() {
-                    cards.remove(index);
+                cards.retain(|card| card.id != id);
+                if cards.len() == self.cards.len() {
+                    return self;
                 }
-                StarData {
-                    priority_cards: self.priority_cards.clone(),
-                    cards,
-                }.save().into()
+                self.change(self.priority_cards.clone(), cards, Some("Card closed".to_string()))
+            },
+            StarAction::MoveCard { id, lane, position } => {
+                self.move_card(id, lane, position).unwrap_or(self)
+            },
+            StarAction::PromoteCard(id) => {
+                self.move_card(id, Lane::Priority, 0).unwrap_or(self)
             },
-            _ => {
+            StarAction::DemoteCard(id) => {
+                self.move_card(id, Lane::Normal, 0).unwrap_or(self)
+            },
+            StarAction::Undo => {
+                let mut history = self.history.clone();
+                match history.undo.pop_back() {
+                    Some(snapshot) => {
+                        history.redo.push(self.snapshot());
+                        self.restore(snapshot, history)
+                    },
+                    None => self,
+                }
+            },
+            StarAction::Redo => {
+                let mut history = self.history.clone();
+                match history.redo.pop() {
+                    Some(snapshot) => {
+                        history.push_undo(self.snapshot());
+                        self.restore(snapshot, history)
+                    },
+                    None => self,
+                }
+            },
+            StarAction::DismissUndoHint => {
+                if self.undo_hint.is_none() {
+                    return self;
+                }
                 StarData {
                     priority_cards: self.priority_cards.clone(),
                     cards: self.cards.clone(),
+                    history: self.history.clone(),
+                    undo_hint: None,
                 }.into()
-            }
+            },
+        }
+    }
+}
+
+// Saves that didn't reach storage, shown to staff until a retry or a later save of the same data succeeds.
+// Stored values that couldn't be read are shown too, until dismissed.
+#[derive(Default, PartialEq)]
+pub struct SaveFailures {
+    pub failures: Vec<SaveError>,
+    pub unreadable: Vec<LoadError>,
+}
+
+pub enum SaveFailureAction {
+    Event(SaveEvent),
+    Retry,
+    Dismiss,
+    DismissUnreadable,
+}
+
+impl Reducible for SaveFailures {
+    type Action = SaveFailureAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        match action {
+            SaveFailureAction::Event(SaveEvent::Failed(err)) => {
+                let mut failures:Vec<SaveError> = self.failures.iter().filter(|failure| failure.key != err.key).cloned().collect();
+                failures.push(err);
+                SaveFailures { failures, unreadable: self.unreadable.clone() }.into()
+            },
+            SaveFailureAction::Event(SaveEvent::Saved(key)) => {
+                if !self.failures.iter().any(|failure| failure.key == key) {
+                    return self;
+                }
+                SaveFailures {
+                    failures: self.failures.iter().filter(|failure| failure.key != key).cloned().collect(),
+                    unreadable: self.unreadable.clone(),
+                }.into()
+            },
+            SaveFailureAction::Event(SaveEvent::Unreadable(err)) => {
+                if self.unreadable.iter().any(|unreadable| unreadable.key == err.key) {
+                    return self;
+                }
+                let mut unreadable = self.unreadable.clone();
+                unreadable.push(err);
+                SaveFailures { failures: self.failures.clone(), unreadable }.into()
+            },
+            SaveFailureAction::Retry => {
+                let backend = storage::backend();
+                SaveFailures {
+                    failures: self.failures.iter().filter_map(|failure| failure.retry(&*backend).err()).collect(),
+                    unreadable: self.unreadable.clone(),
+                }.into()
+            },
+            SaveFailureAction::Dismiss => SaveFailures { failures: Vec::new(), unreadable: self.unreadable.clone() }.into(),
+            SaveFailureAction::DismissUnreadable => SaveFailures { failures: self.failures.clone(), unreadable: Vec::new() }.into(),
         }
     }
 }
@@ -161,7 +447,8 @@ pub struct DaypartTasks {
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Tasks {
     pub tasks: [DaypartTasks; 3],
-    pub date_of_use: DateTime<Utc>,
+    // The store's local date, the tasks start over when it changes.
+    pub date_of_use: NaiveDate,
 }
 
 impl Default for Tasks {
@@ -189,37 +476,43 @@ impl Default for Tasks {
                     ]
                 }
             ],
-            date_of_use: Utc::now(),
+            date_of_use: clock::today().naive_local(),
         }
     }
 }
 
+pub const DAILY_TASKS: Schema<Tasks> = Schema::new("daily_tasks", "Daily tasks", &[storage::wrap_unversioned, daily_tasks_local_date]);
+
+// Version 1 -> 2: the day the tasks are for is the local date instead of the UTC time they were created.
+fn daily_tasks_local_date(mut tasks: Value) -> Result<Value, String> {
+    let created: DateTime<Utc> = serde_json::from_value(tasks.get("date_of_use").cloned().unwrap_or_default()).map_err(|err| err.to_string())?;
+    tasks["date_of_use"] = serde_json::to_value(created.with_timezone(&chrono::Local).naive_local().date()).map_err(|err| err.to_string())?;
+    Ok(tasks)
+}
+
 impl StorableData for Tasks {
-    fn load() -> Self {
-        let storage:Result<Tasks, StorageError> = LocalStorage::get("daily_tasks");
-        match storage {
-            Ok(tasks) => {
-                if tasks.date_of_use.date() == Utc::today() {
-                    tasks
-                }
-                else {
-                    Self::default()
-                }
-            },
-            Err(err) => {
-                log::error!("{:?}", err);
-                Self::default()
-            }
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self {
+        match DAILY_TASKS.load(backend) {
+            Some(tasks) if tasks.date_of_use == clock::today().naive_local() => tasks,
+            _ => Self::default(),
         }
     }
 
-    fn save(self) -> Self {
-        LocalStorage::set("daily_tasks", self.clone());
-        self
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        DAILY_TASKS.save(backend, self)
     }
 }
 
 impl Daypart {
+    // The daypart an hour of the day falls in, going by get_time_period.
+    pub fn at(hour: u32) -> Daypart {
+        match hour {
+            0..=11 => Daypart::Opening,
+            12..=15 => Daypart::Mid,
+            _ => Daypart::Closing,
+        }
+    }
+
     pub fn to_string(self) -> &'static str {
         match self {
             Daypart::Opening => "Opening",
@@ -240,27 +533,101 @@ impl Daypart {
 
 // Timer
 pub enum TimerAction {
-    Start(i32),
+    // Runs until the deadline, which is what gets persisted so the timer stays right across reloads and sleep.
+    Start(DateTime<Utc>),
+    // Freezes the time left until Resume, which pushes the deadline back by however long the pause lasted.
+    Pause,
+    // Also picks a running timer back up after a reload, from the deadline it was created with.
+    Resume,
+    // Counts down to a deadline set earlier, like a kitchen timer's stored one, without repeating what it already announced.
+    Continue(DateTime<Utc>),
     Stop,
-    SetCallback(Callback<u16>)
+    SetCallback(Callback<TimerEvent>)
+}
+
+// What a timer tells TimerData.callback about. Events are emitted while the reducer is running,
+// so a handler must not dispatch to the same timer straight away.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TimerEvent {
+    // Also sent when a paused timer resumes or a running one is picked back up after a reload.
+    Started { deadline: DateTime<Utc> },
+    // Seconds left, once per second while running. Negative once the timer has expired.
+    Tick(i32),
+    // Seconds left when the timer first got within warning_seconds of its deadline.
+    Warning(i32),
+    Expired,
+    Paused(i32),
+    Stopped,
 }
 
 #[derive(Properties, PartialEq, Clone)]
 pub struct TimerData {
-    pub time_left:UseStateHandle<Cell<i32>>, // Seconds,
+    #[prop_or_default]
+    pub deadline:Option<DateTime<Utc>>,
+    pub time_left:UseStateHandle<i32>, // Seconds, refreshed from the deadline while running
+    #[prop_or_default]
+    pub paused_at:Option<DateTime<Utc>>,
+    // Time spent paused since the timer was started, kept apart from the time it actually ran.
+    #[prop_or_default]
+    pub paused_seconds:i32,
     #[prop_or_default]
     pub running:bool,
     #[prop_or_default]
-    pub callback:Callback<u16>,
+    pub callback:Callback<TimerEvent>,
+    // How close to the deadline TimerEvent::Warning is sent.
+    #[prop_or(60)]
+    pub warning_seconds:i32,
     #[prop_or_default]
-    pub timer_interval_id:i32,
-    pub timer_sound:HtmlAudioElement,
+    pub interval:TimerInterval,
+}
+
+// The interval counting a timer down. Every copy of the timer's state shares it, so a component can stop it
+// when it goes away using whichever copy it holds.
+#[derive(Clone, Default)]
+pub struct TimerInterval(Rc<RefCell<Option<Interval>>>);
+
+impl PartialEq for TimerInterval {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
 }
 
+// Says which part of a typed duration was wrong, the Display text is shown under the input.
+#[derive(Clone, PartialEq, Debug)]
 pub enum TimerParseError {
-    InvalidInput
+    Empty,
+    InvalidNumber(String),
+    TooManyParts(String),
+    OutOfRange { part: String, unit: &'static str },
+    MissingUnit(String),
+    UnknownUnit(String),
+    RepeatedUnit(&'static str),
+    TooLong,
+}
+
+impl std::fmt::Display for TimerParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimerParseError::Empty => write!(f, "Enter a time, e.g. 4:00 or 1h 30m."),
+            TimerParseError::InvalidNumber(part) if part.is_empty() => write!(f, "Every part between the colons needs a number."),
+            TimerParseError::InvalidNumber(part) => write!(f, "\"{}\" is not a number.", part),
+            TimerParseError::TooManyParts(time) => write!(f, "\"{}\" has too many parts, H:MM:SS is the longest form.", time),
+            TimerParseError::OutOfRange { part, unit } => write!(f, "\"{}\" is too many {}, it has to be less than 60.", part, unit),
+            TimerParseError::MissingUnit(part) => write!(f, "\"{}\" needs a unit, e.g. h, m or s.", part),
+            TimerParseError::UnknownUnit(unit) => write!(f, "\"{}\" is not a unit, use h, m or s.", unit),
+            TimerParseError::RepeatedUnit(unit) => write!(f, "The {} are given more than once.", unit),
+            TimerParseError::TooLong => write!(f, "That is longer than a timer can run."),
+        }
+    }
 }
 
+// Accepted unit suffixes, with the name used in errors and the seconds in one of them.
+const TIME_UNITS: &[(&[&str], &str, f64)] = &[
+    (&["h", "hr", "hrs", "hour", "hours"], "hours", 3600.0),
+    (&["m", "min", "mins", "minute", "minutes"], "minutes", 60.0),
+    (&["s", "sec", "secs", "second", "seconds"], "seconds", 1.0),
+];
+
 impl TimerData {
     pub fn format_time_left(mut time_left: i32) -> String {
         let mut is_negative:bool = false;
@@ -268,50 +635,176 @@ impl TimerData {
             time_left = time_left.abs();
             is_negative = true;
         }
-        let minutes = time_left / 60;
-        let seconds = time_left - (minutes * 60);
-        (if is_negative { "-" } else { "" }).to_owned() + &format!("{:02}:{:02}", minutes, seconds)
-    }
-
-    
-    pub fn seconds_from_str(time:String) -> Result<u16, TimerParseError>  {
-        let split_time:Vec<&str> = time.split(':').collect();
-        let mut minutes: u16 = 0;
-        let mut seconds: u16 = 0;
-        if split_time.len() > 1 { // Time has minutes
-            // Parse Minutes
-            if let Ok(val) = split_time[0].parse::<u16>() {
-                minutes = val;
+        let hours = time_left / 3600;
+        let minutes = (time_left / 60) % 60;
+        let seconds = time_left % 60;
+        (if is_negative { "-" } else { "" }).to_owned() + &if hours > 0 {
+            format!("{}:{:02}:{:02}", hours, minutes, seconds)
+        } else {
+            format!("{:02}:{:02}", minutes, seconds)
+        }
+    }
+
+    // Reads "SS", "MM:SS", "H:MM:SS" or units like "1h 30m", "90s" and "2.5m". A bare whole number is
+    // seconds, as it always has been, and a bare decimal is minutes.
+    pub fn seconds_from_str(time:&str) -> Result<i32, TimerParseError> {
+        let time = time.trim();
+        if time.is_empty() {
+            return Err(TimerParseError::Empty);
+        }
+        let seconds = if time.contains(':') {
+            Self::clock_seconds(time)?
+        } else {
+            Self::unit_seconds(time)?
+        };
+        if seconds > i32::MAX as f64 {
+            return Err(TimerParseError::TooLong);
+        }
+        Ok(seconds.round() as i32)
+    }
+
+    fn clock_seconds(time:&str) -> Result<f64, TimerParseError> {
+        let parts:Vec<&str> = time.split(':').map(str::trim).collect();
+        if parts.len() > 3 {
+            return Err(TimerParseError::TooManyParts(time.to_string()));
+        }
+        let units = &TIME_UNITS[TIME_UNITS.len() - parts.len()..];
+        let mut seconds = 0.0;
+        for (i, (part, (_, unit, unit_seconds))) in parts.iter().zip(units).enumerate() {
+            let value = part.parse::<u32>().map_err(|err| match err.kind() {
+                IntErrorKind::PosOverflow => TimerParseError::TooLong,
+                _ => TimerParseError::InvalidNumber(part.to_string()),
+            })?;
+            // Only the leading part may run past 60, "90:00" is fine but "1:90" is a typo.
+            if i > 0 && value >= 60 {
+                return Err(TimerParseError::OutOfRange { part: part.to_string(), unit });
+            }
+            seconds += value as f64 * unit_seconds;
+        }
+        Ok(seconds)
+    }
+
+    fn unit_seconds(time:&str) -> Result<f64, TimerParseError> {
+        let mut rest = time;
+        let mut seen:Vec<&'static str> = Vec::new();
+        let mut seconds = 0.0;
+        let mut parts = 0;
+        while !rest.is_empty() {
+            let number_end = rest.find(|c:char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+            let (number, after) = rest.split_at(number_end);
+            if number.is_empty() {
+                let word = rest.split_whitespace().next().unwrap_or(rest);
+                return Err(TimerParseError::InvalidNumber(word.to_string()));
             }
-            else {
-                return Err(TimerParseError::InvalidInput);
+            let after = after.trim_start();
+            let unit_end = after.find(|c:char| !c.is_alphabetic()).unwrap_or(after.len());
+            let (unit, after) = after.split_at(unit_end);
+            rest = after.trim_start();
+            parts += 1;
+
+            let value = number.parse::<f64>().map_err(|_| TimerParseError::InvalidNumber(number.to_string()))?;
+            // Whatever follows a number that isn't a letter or another number, like the - in "5-", is taken as its unit.
+            if unit.is_empty() && !rest.is_empty() && !rest.starts_with(|c:char| c.is_ascii_digit() || c == '.') {
+                let junk = rest.split_whitespace().next().unwrap_or(rest);
+                return Err(TimerParseError::UnknownUnit(junk.to_string()));
             }
-            // Parse seconds
-            if let Ok(val) = split_time[1].parse::<u16>() {
-                seconds = val;
+            if unit.is_empty() {
+                if parts > 1 || !rest.is_empty() {
+                    return Err(TimerParseError::MissingUnit(number.to_string()));
+                }
+                return Ok(if number.contains('.') { value * 60.0 } else { value });
             }
-            else {
-                return Err(TimerParseError::InvalidInput);
+            let lower = unit.to_lowercase();
+            let (_, name, unit_seconds) = TIME_UNITS.iter()
+                .find(|(names, _, _)| names.contains(&lower.as_str()))
+                .ok_or_else(|| TimerParseError::UnknownUnit(unit.to_string()))?;
+            if seen.contains(name) {
+                return Err(TimerParseError::RepeatedUnit(name));
             }
+            seen.push(name);
+            seconds += value * unit_seconds;
         }
-        else {
-            // Parse seconds
-            if let Ok(val) = split_time[0].parse::<u16>() {
-                seconds = val;
+        Ok(seconds)
+    }
+
+    pub fn deadline_in(seconds:i32) -> DateTime<Utc> {
+        clock::now() + Duration::seconds(seconds.into())
+    }
+
+    // Whole seconds until the deadline, negative once it has passed.
+    pub fn seconds_until(deadline:DateTime<Utc>) -> i32 {
+        Self::seconds_between(clock::now(), deadline)
+    }
+
+    pub fn seconds_between(from:DateTime<Utc>, deadline:DateTime<Utc>) -> i32 {
+        let millis = (deadline - from).num_milliseconds();
+        // Round up so the timer shows 00:00 for the last second rather than the first.
+        (millis as f64 / 1000.0).ceil() as i32
+    }
+
+    // Seconds left when the timer was paused, or right now when it's running.
+    pub fn seconds_left(&self) -> Option<i32> {
+        let deadline = self.deadline?;
+        Some(Self::seconds_between(self.paused_at.unwrap_or_else(clock::now), deadline))
+    }
+
+    // Paused time including the pause that's still going on.
+    pub fn total_paused_seconds(&self) -> i32 {
+        self.paused_seconds + self.paused_at.map_or(0, |paused_at| (clock::now() - paused_at).num_seconds() as i32)
+    }
+
+    // What to report when the time left goes from shown to remaining, shown being None when a timer is started.
+    // A start reports an expiry that has already passed. The warning only goes out as the countdown crosses
+    // into its window, not when a timer is started inside it.
+    fn threshold_event(shown:Option<i32>, remaining:i32, warning_seconds:i32) -> Option<TimerEvent> {
+        if shown.is_none_or(|shown| shown > 0) && remaining <= 0 {
+            Some(TimerEvent::Expired)
+        } else if shown.is_some_and(|shown| shown > warning_seconds) && remaining <= warning_seconds {
+            Some(TimerEvent::Warning(remaining))
+        } else {
+            None
+        }
+    }
+
+    // Where a resumed timer carries on from: the deadline pushed back by the pause, the time paused so far and
+    // the seconds it showed when it was paused, so what it announced before the pause isn't announced again.
+    fn resume_point(deadline:DateTime<Utc>, paused_at:Option<DateTime<Utc>>, paused_seconds:i32) -> (DateTime<Utc>, i32, i32) {
+        match paused_at {
+            Some(paused_at) => {
+                let paused = clock::now() - paused_at;
+                (deadline + paused, paused_seconds + paused.num_seconds() as i32, Self::seconds_between(paused_at, deadline))
+            },
+            // Picked back up after a reload, it kept counting down while the page was closed.
+            None => (deadline, paused_seconds, Self::seconds_until(deadline)),
+        }
+    }
+
+    // Starts the interval that counts down to the deadline, from the seconds last shown.
+    fn run(&self, deadline:DateTime<Utc>, mut shown:Option<i32>) {
+        self.stop();
+        let time_left = self.time_left.clone();
+        let callback = self.callback.clone();
+        let warning_seconds = self.warning_seconds;
+        let mut tick = move || {
+            let remaining = Self::seconds_until(deadline);
+            if shown == Some(remaining) {
+                return;
             }
-            else {
-                return Err(TimerParseError::InvalidInput);
+            if let Some(event) = Self::threshold_event(shown, remaining, warning_seconds) {
+                callback.emit(event);
             }
-        }
-        Ok((minutes * 60) + seconds)
+            shown = Some(remaining);
+            time_left.set(remaining);
+            callback.emit(TimerEvent::Tick(remaining));
+        };
+        tick();
+        // Only the deadline matters, so a throttled or suspended interval catches up on its next tick.
+        *self.interval.0.borrow_mut() = Some(Interval::new(350, tick));
     }
 
+    // Dropping the interval cancels it.
     pub fn stop(&self) {
-        if self.timer_interval_id > -1 {
-            if let Some(window) = window() {
-                window.clear_interval_with_handle(self.timer_interval_id);
-            }
-        }
+        self.interval.0.borrow_mut().take();
     }
 }
 
@@ -320,46 +813,331 @@ impl Reducible for TimerData {
 
     fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
         match action {
-            TimerAction::Start(st) => {
-                let mut last_time = Instant::now();
-                let time_left = self.time_left.clone();
-                (*time_left).set(st - 1);
-                let timer_interval = Interval::new(350, move || {
-                    if last_time.elapsed().as_secs() >= 1 {
-                        (*time_left).set((*time_left).get() - last_time.elapsed().as_secs() as i32);
-                        time_left.set((*time_left).clone());
-                        last_time = Instant::now();
-                    }
-                });
-                let id = timer_interval.forget();
-
+            TimerAction::Start(deadline) => {
+                self.callback.emit(TimerEvent::Started { deadline });
+                self.run(deadline, None);
+                Self {
+                    deadline: Some(deadline),
+                    paused_at: None,
+                    paused_seconds: 0,
+                    running: true,
+                    ..(*self).clone()
+                }.into()
+            },
+            TimerAction::Pause => {
+                if !self.running {
+                    return self;
+                }
+                self.stop();
+                let paused_at = clock::now();
+                let paused = Self { paused_at: Some(paused_at), running: false, ..(*self).clone() };
+                if let Some(seconds_left) = paused.seconds_left() {
+                    self.time_left.set(seconds_left);
+                    self.callback.emit(TimerEvent::Paused(seconds_left));
+                }
+                paused.into()
+            },
+            TimerAction::Resume => {
+                let deadline = match self.deadline {
+                    Some(deadline) => deadline,
+                    None => return self,
+                };
+                let (deadline, paused_seconds, shown) = Self::resume_point(deadline, self.paused_at, self.paused_seconds);
+                self.callback.emit(TimerEvent::Started { deadline });
+                self.run(deadline, Some(shown));
+                Self {
+                    deadline: Some(deadline),
+                    paused_at: None,
+                    paused_seconds,
+                    running: true,
+                    ..(*self).clone()
+                }.into()
+            },
+            TimerAction::Continue(deadline) => {
+                self.callback.emit(TimerEvent::Started { deadline });
+                self.run(deadline, Some(Self::seconds_until(deadline)));
                 Self {
-                    time_left:self.time_left.clone(),
+                    deadline: Some(deadline),
+                    paused_at: None,
                     running: true,
-                    callback:self.callback.clone(),
-                    timer_interval_id: id,
-                    timer_sound: self.timer_sound.clone()
+                    ..(*self).clone()
                 }.into()
             },
             TimerAction::Stop => {
                 self.stop();
+                if self.deadline.is_some() {
+                    self.callback.emit(TimerEvent::Stopped);
+                }
                 Self {
-                    time_left:self.time_left.clone(),
+                    deadline: None,
+                    paused_at: None,
+                    paused_seconds: 0,
                     running: false,
-                    callback:self.callback.clone(),
-                    timer_interval_id: -1,
-                    timer_sound: self.timer_sound.clone()
+                    ..(*self).clone()
                 }.into()
             },
             TimerAction::SetCallback(callback) => {
-                Self {
-                    time_left: self.time_left.clone(),
-                    running: self.running,
+                let data = Self {
                     callback,
-                    timer_interval_id: self.timer_interval_id,
-                    timer_sound: self.timer_sound.clone()
-                }.into()
+                    ..(*self).clone()
+                };
+                // The running interval holds on to the old callback, so it's restarted with the new one.
+                if let (true, Some(deadline)) = (self.running, self.deadline) {
+                    data.run(deadline, Some(Self::seconds_until(deadline)));
+                }
+                data.into()
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::storage::{BackendError, MemoryBackend};
+
+    #[test]
+    fn tasks_are_kept_for_the_day() {
+        clock::fake_local(2024, 3, 5, 9, 0);
+        let backend = MemoryBackend::default();
+        let mut tasks = Tasks::load(&backend);
+        tasks.tasks[1].daypart_tasks[0].complete();
+        tasks.save(&backend).unwrap();
+
+        assert!(Tasks::load(&backend) == tasks);
+    }
+
+    #[test]
+    fn tasks_reset_on_a_new_day() {
+        let clock = clock::fake_local(2024, 3, 5, 9, 0);
+        let backend = MemoryBackend::default();
+        let mut tasks = Tasks::load(&backend);
+        tasks.tasks[1].daypart_tasks[0].complete();
+        tasks.save(&backend).unwrap();
+
+        clock.advance(Duration::days(1));
+        let tasks = Tasks::load(&backend);
+        assert!(!tasks.tasks[1].daypart_tasks[0].completed);
+        assert_eq!(tasks.date_of_use, clock::today().naive_local());
+
+        // West of UTC, late evening is already the next day in UTC, the tasks are still kept until local midnight.
+        let clock = clock::fake_local(2024, 3, 5, 9, 0);
+        let mut tasks = Tasks::load(&backend);
+        tasks.tasks[1].daypart_tasks[0].complete();
+        tasks.save(&backend).unwrap();
+        clock.advance(Duration::hours(14) + Duration::minutes(30));
+        assert!(Tasks::load(&backend).tasks[1].daypart_tasks[0].completed);
+        clock.advance(Duration::hours(1));
+        assert!(!Tasks::load(&backend).tasks[1].daypart_tasks[0].completed);
+    }
+
+    #[test]
+    fn stored_tasks_move_to_their_local_date() {
+        clock::fake_local(2024, 3, 5, 23, 30);
+        let stored = serde_json::json!({ "tasks": Tasks::default().tasks, "date_of_use": clock::now() });
+        assert_eq!(DAILY_TASKS.upgrade(&stored.to_string()).unwrap().date_of_use, clock::today().naive_local());
+    }
+
+    #[test]
+    fn timer_durations_parse() {
+        for (time, seconds) in [("90", 90), ("1:30", 90), (" 1:00:00 ", 3600), ("90:00", 5400), ("1h 30m", 5400), ("1H30M", 5400), ("2.5m", 150), ("1.5", 90), ("90 secs", 90)] {
+            assert_eq!(TimerData::seconds_from_str(time), Ok(seconds), "{}", time);
+        }
+    }
+
+    #[test]
+    fn timer_duration_errors() {
+        let error = |time: &str| TimerData::seconds_from_str(time).unwrap_err();
+        assert_eq!(error("  "), TimerParseError::Empty);
+        assert_eq!(error("abc"), TimerParseError::InvalidNumber("abc".to_string()));
+        assert_eq!(error("1:xx"), TimerParseError::InvalidNumber("xx".to_string()));
+        assert_eq!(error(":30"), TimerParseError::InvalidNumber(String::new()));
+        assert_eq!(error("1:2:3:4"), TimerParseError::TooManyParts("1:2:3:4".to_string()));
+        assert_eq!(error("1:90"), TimerParseError::OutOfRange { part: "90".to_string(), unit: "seconds" });
+        assert_eq!(error("1h 30"), TimerParseError::MissingUnit("30".to_string()));
+        assert_eq!(error("30 5m"), TimerParseError::MissingUnit("30".to_string()));
+        assert_eq!(error("5 days"), TimerParseError::UnknownUnit("days".to_string()));
+        assert_eq!(error("5-"), TimerParseError::UnknownUnit("-".to_string()));
+        assert_eq!(error("1m 2min"), TimerParseError::RepeatedUnit("minutes"));
+        assert_eq!(error("600000h"), TimerParseError::TooLong);
+        assert_eq!(error("99999999999:00"), TimerParseError::TooLong);
+    }
+
+    #[test]
+    fn timers_warn_when_crossing_into_the_window() {
+        assert_eq!(TimerData::threshold_event(Some(61), 60, 60), Some(TimerEvent::Warning(60)));
+        assert_eq!(TimerData::threshold_event(Some(60), 59, 60), None);
+        assert_eq!(TimerData::threshold_event(Some(90), 30, 60), Some(TimerEvent::Warning(30)));
+        // Started with less than the warning time left.
+        assert_eq!(TimerData::threshold_event(None, 30, 60), None);
+    }
+
+    #[test]
+    fn timers_expire_once() {
+        assert_eq!(TimerData::threshold_event(Some(1), 0, 60), Some(TimerEvent::Expired));
+        assert_eq!(TimerData::threshold_event(Some(0), -1, 60), None);
+        // Started with a deadline that has already passed.
+        assert_eq!(TimerData::threshold_event(None, -300, 60), Some(TimerEvent::Expired));
+        assert_eq!(TimerData::threshold_event(Some(120), -5, 60), Some(TimerEvent::Expired));
+    }
+
+    #[test]
+    fn history_cards_keep_one_id_across_snapshots() {
+        let snapshot = |cards: Value| serde_json::json!({ "priority_cards": [], "cards": cards });
+        let history = serde_json::json!({
+            "undo": [
+                snapshot(serde_json::json!([{ "card_type": "StartNewTask", "is_priority": false, "index": 0 }])),
+                snapshot(serde_json::json!([{ "card_type": "StartNewTask", "is_priority": false, "index": 0 }, { "card_type": "CsCycle", "is_priority": false, "index": 1 }])),
+            ],
+            "redo": [
+                snapshot(serde_json::json!([{ "card_type": "CsCycle", "is_priority": false, "index": 0 }, { "card_type": "StartNewTask", "is_priority": false, "index": 1 }])),
+            ],
+        });
+        let history = HISTORY.upgrade(&history.to_string()).unwrap();
+        let ids = |snapshot: &CardsSnapshot| snapshot.cards.iter().map(|card| (card.card_type.clone(), card.id)).collect::<Vec<_>>();
+        let launcher = history.undo[0].cards[0].id;
+        let cycle = history.undo[1].cards[1].id;
+        assert_ne!(launcher, cycle);
+        assert_eq!(ids(&history.undo[1]), vec![(CardType::START_NEW_TASK, launcher), (CardType::new("CsCycle"), cycle)]);
+        assert_eq!(ids(&history.redo[0]), vec![(CardType::new("CsCycle"), cycle), (CardType::START_NEW_TASK, launcher)]);
+    }
+
+    #[test]
+    fn unclosable_cards_stay_in_their_lane() {
+        let data = Rc::new(StarData::load(&MemoryBackend::default()));
+        let launcher = data.cards[0].id;
+        assert!(data.step_position(launcher, true).is_none());
+        assert!(data.drop_position(launcher, DropTarget::Lane(Lane::Priority)).is_none());
+        let data = data.reduce(StarAction::PromoteCard(launcher));
+        assert!(data.priority_cards.is_empty());
+
+        let data = data.reduce(StarAction::AddCard(CardType::new("CsCycle")));
+        let cycle = data.cards[1].id;
+        let data = data.reduce(StarAction::PromoteCard(cycle));
+        assert_eq!(data.find(cycle), Some((Lane::Priority, 0)));
+    }
+
+    #[test]
+    fn save_failures_stay_until_saved_or_retried() {
+        let failed = |key: &str| SaveEvent::Failed(SaveError { key: key.to_owned(), label: "Urns", payload: Some("{}".to_owned()), error: BackendError::QuotaExceeded });
+        let failures = Rc::new(SaveFailures::default())
+            .reduce(SaveFailureAction::Event(failed("urns")))
+            .reduce(SaveFailureAction::Event(failed("urns")))
+            .reduce(SaveFailureAction::Event(failed("cards")));
+        assert_eq!(failures.failures.iter().map(|failure| failure.key.as_str()).collect::<Vec<_>>(), vec!["urns", "cards"]);
+
+        let failures = failures.reduce(SaveFailureAction::Event(SaveEvent::Saved("urns".to_owned())));
+        assert_eq!(failures.failures.len(), 1);
+        let failures = failures.reduce(SaveFailureAction::Retry);
+        assert!(failures.failures.is_empty());
+        assert_eq!(storage::backend().get("cards").as_deref(), Some("{}"));
+    }
+
+
+    #[test]
+    fn undo_and_redo_step_through_the_history() {
+        let data = Rc::new(StarData::load(&MemoryBackend::default()));
+        let added = data.clone().reduce(StarAction::AddCard(CardType::new("CsCycle")));
+        let cycle = added.cards[1].id;
+        let closed = added.clone().reduce(StarAction::DestroyCard(cycle));
+        assert_eq!(closed.undo_hint.as_deref(), Some("Card closed"));
+
+        let undone = closed.clone().reduce(StarAction::Undo);
+        assert!(undone.cards == added.cards);
+        assert!(undone.undo_hint.is_none());
+        let undone = undone.reduce(StarAction::Undo);
+        assert!(undone.cards == data.cards);
+        assert!(Rc::ptr_eq(&undone.clone().reduce(StarAction::Undo), &undone));
+
+        let redone = undone.reduce(StarAction::Redo).reduce(StarAction::Redo);
+        assert!(redone.cards == closed.cards);
+        assert!(redone.history.redo.is_empty());
+
+        // A new action drops whatever could still be redone.
+        let changed = redone.reduce(StarAction::Undo).reduce(StarAction::AddCard(CardType::new("Daydots")));
+        assert!(changed.history.redo.is_empty());
+        assert_eq!(changed.history.undo.len(), 2);
+    }
+
+    #[test]
+    fn history_keeps_the_last_actions() {
+        let mut data = Rc::new(StarData::load(&MemoryBackend::default()));
+        for _ in 0..HISTORY_LIMIT + 5 {
+            data = data.reduce(StarAction::AddCard(CardType::new("CsCycle")));
+        }
+        assert_eq!(data.history.undo.len(), HISTORY_LIMIT);
+        for _ in 0..HISTORY_LIMIT + 5 {
+            data = data.reduce(StarAction::Undo);
+        }
+        assert_eq!(data.cards.len(), 6);
+        assert_eq!(data.history.redo.len(), HISTORY_LIMIT);
+    }
+
+    #[test]
+    fn history_survives_a_reload() {
+        let backend = MemoryBackend::default();
+        let data = Rc::new(StarData::load(&backend));
+        let added = data.clone().reduce(StarAction::AddCard(CardType::new("CsCycle")));
+        added.save(&backend).unwrap();
+
+        // Callbacks aren't stored, so the cards are told apart by id.
+        let ids = |cards: &VecDeque<CardData>| cards.iter().map(|card| card.id).collect::<Vec<_>>();
+        let reloaded = Rc::new(StarData::load(&backend));
+        assert_eq!(reloaded.history.undo.len(), 1);
+        assert_eq!(ids(&reloaded.cards), ids(&added.cards));
+        assert_eq!(ids(&reloaded.reduce(StarAction::Undo).cards), ids(&data.cards));
+    }
+
+    #[test]
+    fn single_instance_cards_are_only_added_once() {
+        let data = Rc::new(StarData::load(&MemoryBackend::default())).reduce(StarAction::AddCard(CardType::new("Daydots")));
+        let daydots = data.cards[1].id;
+        let data = data.reduce(StarAction::PromoteCard(daydots));
+        let data = data.clone().reduce(StarAction::AddCard(CardType::new("Daydots")));
+        assert_eq!(data.cards.len() + data.priority_cards.len(), 2);
+    }
+
+    #[test]
+    fn resumed_timers_dont_announce_again() {
+        let clock = clock::fake_local(2024, 3, 5, 9, 0);
+        let overdue = clock::now() - Duration::seconds(30);
+        let paused_at = clock::now();
+        clock.advance(Duration::minutes(5));
+        let (deadline, paused_seconds, shown) = TimerData::resume_point(overdue, Some(paused_at), 10);
+        assert_eq!((deadline, paused_seconds, shown), (overdue + Duration::minutes(5), 310, -30));
+        assert_eq!(TimerData::threshold_event(Some(shown), TimerData::seconds_until(deadline), 60), None);
+
+        // Paused before it ran out, it still warns and expires on the way down.
+        let (deadline, _, shown) = TimerData::resume_point(clock::now() + Duration::seconds(90), Some(clock::now()), 0);
+        assert_eq!(shown, 90);
+        clock.advance(Duration::seconds(30));
+        assert_eq!(TimerData::threshold_event(Some(shown), TimerData::seconds_until(deadline), 60), Some(TimerEvent::Warning(60)));
+        clock.advance(Duration::seconds(60));
+        assert_eq!(TimerData::threshold_event(Some(60), TimerData::seconds_until(deadline), 60), Some(TimerEvent::Expired));
+
+        // Picked up overdue after a reload.
+        let (deadline, _, shown) = TimerData::resume_point(overdue, None, 0);
+        assert_eq!(TimerData::threshold_event(Some(shown), TimerData::seconds_until(deadline), 60), None);
+    }
+
+    #[test]
+    fn pauses_push_the_deadline_back_and_add_up() {
+        let clock = clock::fake_local(2024, 3, 5, 9, 0);
+        let deadline = clock::now() + Duration::seconds(600);
+        clock.advance(Duration::seconds(100));
+        let paused_at = clock::now();
+        clock.advance(Duration::seconds(60));
+        let (deadline, paused_seconds, shown) = TimerData::resume_point(deadline, Some(paused_at), 0);
+        assert_eq!((paused_seconds, shown), (60, 500));
+        assert_eq!(TimerData::seconds_until(deadline), 500);
+
+        clock.advance(Duration::seconds(100));
+        let paused_at = clock::now();
+        clock.advance(Duration::seconds(30));
+        let (deadline, paused_seconds, shown) = TimerData::resume_point(deadline, Some(paused_at), paused_seconds);
+        assert_eq!((paused_seconds, shown), (90, 400));
+        assert_eq!(TimerData::seconds_until(deadline), 400);
+    }
+}
@@ -1,4 +1,949 @@
+use std::{cell::{Cell, RefCell}, collections::BTreeMap, marker::PhantomData, rc::Rc};
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use serde_json::Value;
+use wasm_bindgen::{JsCast, JsValue, closure::Closure};
+use yew::Callback;
+use web_sys::{window, DomException, IdbDatabase, IdbObjectStore, IdbRequest, IdbTransaction, IdbTransactionMode};
+
+use crate::{alarm, checklist, clock, history, products, roasts, rotation, state, components, timers, urns};
+
 pub trait StorableData where Self: Sized {
-    fn load() -> Self;
-    fn save(self) -> Self { self }
-}
\ No newline at end of file
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self;
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError>;
+
+    // Saves to the app's backend. Failures are passed on to the save listener by the schema, not returned.
+    fn persist(&self) {
+        if let Err(err) = self.save(&*backend()) {
+            log::error!("{}", err);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendError {
+    Unavailable,
+    QuotaExceeded,
+    Serialize(String),
+    Write(String),
+}
+
+impl std::fmt::Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendError::Unavailable => write!(f, "storage is turned off in this browser"),
+            BackendError::QuotaExceeded => write!(f, "storage is full"),
+            BackendError::Serialize(reason) => write!(f, "could not serialize value ({})", reason),
+            BackendError::Write(reason) => write!(f, "could not write value ({})", reason),
+        }
+    }
+}
+
+impl From<JsValue> for BackendError {
+    fn from(err: JsValue) -> Self {
+        match err.dyn_ref::<DomException>() {
+            Some(exception) if exception.name() == "QuotaExceededError" => BackendError::QuotaExceeded,
+            _ => BackendError::Write(format!("{:?}", err)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveError {
+    pub key: String,
+    pub label: &'static str,
+    // What should have been written, kept so the save can be retried.
+    pub payload: Option<String>,
+    pub error: BackendError,
+}
+
+impl SaveError {
+    pub fn retry<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        match &self.payload {
+            Some(payload) => backend.set(&self.key, payload).map_err(|error| SaveError { error, ..self.clone() }),
+            None => Err(self.clone()),
+        }
+    }
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.label, self.error)
+    }
+}
+
+// A stored value that couldn't be migrated, set aside so it can be recovered once the migration is fixed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadError {
+    pub key: String,
+    pub label: &'static str,
+    pub backup_key: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}), kept under \"{}\"", self.label, self.reason, self.backup_key)
+    }
+}
+
+pub enum SaveEvent {
+    Saved(String),
+    Failed(SaveError),
+    Unreadable(LoadError),
+}
+
+thread_local! {
+    static SAVE_LISTENER: RefCell<Option<Callback<SaveEvent>>> = const { RefCell::new(None) };
+    // Failures from before there is a listener, like values that can't be loaded while the app starts up.
+    static PENDING_EVENTS: RefCell<Vec<SaveEvent>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn set_save_listener(listener: Option<Callback<SaveEvent>>) {
+    if let Some(listener) = &listener {
+        for event in PENDING_EVENTS.with(|pending| pending.take()) {
+            listener.emit(event);
+        }
+    }
+    SAVE_LISTENER.with(|current| *current.borrow_mut() = listener);
+}
+
+fn notify(event: SaveEvent) {
+    let event = SAVE_LISTENER.with(|listener| match &*listener.borrow() {
+        Some(listener) => {
+            listener.emit(event);
+            None
+        },
+        None => Some(event),
+    });
+    if let Some(event) = event {
+        if !matches!(event, SaveEvent::Saved(_)) {
+            PENDING_EVENTS.with(|pending| pending.borrow_mut().push(event));
+        }
+    }
+}
+
+// Raw key/value store the versioned schemas are written to.
+pub trait StorageBackend {
+    fn get(&self, key: &str) -> Option<String>;
+    fn set(&self, key: &str, value: &str) -> Result<(), BackendError>;
+    fn remove(&self, key: &str);
+    fn keys(&self) -> Vec<String>;
+
+    // Stores every change or none of them, None removes the key. on_done is called once the changes
+    // are stored for good, or have been rolled back.
+    fn apply(&self, changes: Vec<(String, Option<String>)>, on_done: Box<dyn FnOnce(Result<(), BackendError>)>) {
+        let previous: Vec<(String, Option<String>)> = changes.iter().map(|(key, _)| (key.clone(), self.get(key))).collect();
+        let result = changes.iter().try_for_each(|(key, value)| match value {
+            Some(value) => self.set(key, value),
+            None => {
+                self.remove(key);
+                Ok(())
+            },
+        });
+        if result.is_err() {
+            for (key, value) in previous {
+                match value {
+                    Some(value) => if let Err(err) = self.set(&key, &value) {
+                        log::error!("{}", err);
+                    },
+                    None => self.remove(&key),
+                }
+            }
+        }
+        on_done(result);
+    }
+}
+
+pub struct LocalBackend;
+
+impl LocalBackend {
+    // LocalStorage can be missing entirely when storage is disabled, gloo's LocalStorage panics in that case.
+    fn storage() -> Result<web_sys::Storage, BackendError> {
+        window().and_then(|window| window.local_storage().ok().flatten()).ok_or(BackendError::Unavailable)
+    }
+}
+
+impl StorageBackend for LocalBackend {
+    fn get(&self, key: &str) -> Option<String> {
+        Self::storage().ok()?.get_item(key).ok().flatten()
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), BackendError> {
+        Ok(Self::storage()?.set_item(key, value)?)
+    }
+
+    fn remove(&self, key: &str) {
+        if let Ok(storage) = Self::storage() {
+            if let Err(err) = storage.remove_item(key) {
+                log::error!("{:?}", err);
+            }
+        }
+    }
+
+    fn keys(&self) -> Vec<String> {
+        match Self::storage() {
+            Ok(storage) => {
+                let length = storage.length().unwrap_or_default();
+                (0..length).filter_map(|index| storage.key(index).ok().flatten()).collect()
+            },
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+// Used natively and in tests, clones share the same values.
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    values: Rc<RefCell<BTreeMap<String, String>>>,
+}
+
+impl StorageBackend for MemoryBackend {
+    fn get(&self, key: &str) -> Option<String> {
+        self.values.borrow().get(key).cloned()
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), BackendError> {
+        self.values.borrow_mut().insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) {
+        self.values.borrow_mut().remove(key);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.values.borrow().keys().cloned().collect()
+    }
+}
+
+const IDB_NAME: &str = "star";
+const IDB_STORE: &str = "values";
+
+// Kept in LocalStorage while a session runs on it because IndexedDB couldn't be opened,
+// so the next session that gets IndexedDB back knows LocalStorage has the newer values.
+pub const FALLBACK_KEY: &str = "star_fallback";
+
+type OnReady = Box<dyn FnOnce(Result<IndexedDbBackend, BackendError>)>;
+type Handlers = Rc<RefCell<Vec<Closure<dyn FnMut(web_sys::Event)>>>>;
+
+// IndexedDB is asynchronous, so every value is read into memory when the database is opened
+// and writes go to the cache first and to the database in the background.
+pub struct IndexedDbBackend {
+    db: IdbDatabase,
+    cache: MemoryBackend,
+}
+
+impl IndexedDbBackend {
+    pub fn open(on_ready: impl FnOnce(Result<IndexedDbBackend, BackendError>) + 'static) {
+        let on_ready: Rc<RefCell<Option<OnReady>>> = Rc::new(RefCell::new(Some(Box::new(on_ready))));
+        let finish = move |result| {
+            if let Some(on_ready) = on_ready.borrow_mut().take() {
+                on_ready(result);
+            }
+        };
+        let request = match window().and_then(|window| window.indexed_db().ok().flatten()).map(|factory| factory.open_with_u32(IDB_NAME, 1)) {
+            Some(Ok(request)) => request,
+            Some(Err(err)) => return finish(Err(err.into())),
+            None => return finish(Err(BackendError::Unavailable)),
+        };
+
+        let on_upgrade = {
+            let request = request.clone();
+            Closure::once_into_js(move |_: web_sys::Event| {
+                if let Ok(db) = request.result() {
+                    if let Err(err) = db.unchecked_into::<IdbDatabase>().create_object_store(IDB_STORE) {
+                        log::error!("{:?}", err);
+                    }
+                }
+            })
+        };
+        let on_error = {
+            let request = request.clone();
+            let finish = finish.clone();
+            Closure::once_into_js(move |_: web_sys::Event| finish(Err(request_error(&request))))
+        };
+        // Another tab holding an older version open would keep the app waiting, it falls back to LocalStorage instead.
+        let on_blocked = {
+            let finish = finish.clone();
+            Closure::once_into_js(move |_: web_sys::Event| finish(Err(BackendError::Unavailable)))
+        };
+        let on_success = {
+            let request = request.clone();
+            Closure::once_into_js(move |_: web_sys::Event| {
+                match request.result() {
+                    Ok(db) => Self::read_all(db.unchecked_into(), finish),
+                    Err(err) => finish(Err(err.into())),
+                }
+            })
+        };
+        request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+        request.set_onblocked(Some(on_blocked.unchecked_ref()));
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+    }
+
+    // Every way this can fail has to end up in finish, the app only starts once it's called.
+    fn read_all(db: IdbDatabase, finish: impl Fn(Result<IndexedDbBackend, BackendError>) + Clone + 'static) {
+        let requests = db.transaction_with_str(IDB_STORE)
+            .and_then(|transaction| {
+                let store = transaction.object_store(IDB_STORE)?;
+                Ok((transaction, store.get_all_keys()?, store.get_all()?))
+            });
+        let (transaction, keys, values) = match requests {
+            Ok(requests) => requests,
+            Err(err) => return finish(Err(err.into())),
+        };
+        let on_request_error = |request: &IdbRequest| {
+            let request = request.clone();
+            let finish = finish.clone();
+            Closure::once_into_js(move |_: web_sys::Event| finish(Err(request_error(&request))))
+        };
+        keys.set_onerror(Some(on_request_error(&keys).unchecked_ref()));
+        values.set_onerror(Some(on_request_error(&values).unchecked_ref()));
+        // A transaction can also be aborted without any of its requests failing, like when the browser runs out of space.
+        let on_transaction_error = || {
+            let transaction = transaction.clone();
+            let finish = finish.clone();
+            Closure::once_into_js(move |_: web_sys::Event| finish(Err(transaction_error(&transaction))))
+        };
+        transaction.set_onerror(Some(on_transaction_error().unchecked_ref()));
+        transaction.set_onabort(Some(on_transaction_error().unchecked_ref()));
+        // Requests in a transaction complete in order, so the keys are ready once the values are.
+        let on_values = {
+            let values_request = values.clone();
+            Closure::once_into_js(move |_: web_sys::Event| {
+                let cache = MemoryBackend::default();
+                let keys: js_sys::Array = keys.result().map(JsCast::unchecked_into).unwrap_or_else(|_| js_sys::Array::new());
+                let values: js_sys::Array = values_request.result().map(JsCast::unchecked_into).unwrap_or_else(|_| js_sys::Array::new());
+                for (key, value) in keys.iter().zip(values.iter()) {
+                    if let (Some(key), Some(value)) = (key.as_string(), value.as_string()) {
+                        let _ = cache.set(&key, &value);
+                    }
+                }
+                finish(Ok(IndexedDbBackend { db, cache }))
+            })
+        };
+        values.set_onsuccess(Some(on_values.unchecked_ref()));
+    }
+
+    // Runs write in one transaction, so either all of it is stored or none of it. on_done is called
+    // once the transaction has committed or aborted, a failed request aborts it too.
+    fn transact(&self, write: impl FnOnce(&IdbObjectStore) -> Result<(), JsValue>, on_done: impl FnOnce(Result<(), BackendError>) + 'static) {
+        let transaction = match self.db.transaction_with_str_and_mode(IDB_STORE, IdbTransactionMode::Readwrite) {
+            Ok(transaction) => transaction,
+            Err(err) => return on_done(Err(err.into())),
+        };
+        if let Err(err) = transaction.object_store(IDB_STORE).and_then(|store| write(&store)) {
+            let _ = transaction.abort();
+            return on_done(Err(err.into()));
+        }
+        // The handlers are dropped once one of them has run, which also breaks the cycle between them and this list.
+        let handlers: Handlers = Rc::default();
+        let on_done = Rc::new(RefCell::new(Some(on_done)));
+        let finish = {
+            let handlers = handlers.clone();
+            move |result| {
+                if let Some(on_done) = on_done.borrow_mut().take() {
+                    on_done(result);
+                }
+                drop(handlers.take());
+            }
+        };
+        let on_complete = {
+            let finish = finish.clone();
+            Closure::<dyn FnMut(web_sys::Event)>::new(move |_| finish(Ok(())))
+        };
+        let on_abort = {
+            let transaction = transaction.clone();
+            Closure::<dyn FnMut(web_sys::Event)>::new(move |_| finish(Err(transaction_error(&transaction))))
+        };
+        transaction.set_oncomplete(Some(on_complete.as_ref().unchecked_ref()));
+        transaction.set_onabort(Some(on_abort.as_ref().unchecked_ref()));
+        handlers.borrow_mut().extend([on_complete, on_abort]);
+    }
+}
+
+fn request_error(request: &IdbRequest) -> BackendError {
+    match request.error() {
+        Ok(Some(err)) => JsValue::from(err).into(),
+        _ => BackendError::Unavailable,
+    }
+}
+
+fn transaction_error(transaction: &IdbTransaction) -> BackendError {
+    match transaction.error() {
+        Some(err) => JsValue::from(err).into(),
+        None => BackendError::Unavailable,
+    }
+}
+
+impl StorageBackend for IndexedDbBackend {
+    fn get(&self, key: &str) -> Option<String> {
+        self.cache.get(key)
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), BackendError> {
+        // Reported like a failed save, so it shows up with the other save failures and can be retried.
+        let failed = SaveError {
+            key: key.to_owned(),
+            label: stored_for(key).map_or("Saved data", |stored| stored.label()),
+            payload: Some(value.to_owned()),
+            error: BackendError::Unavailable,
+        };
+        self.transact(
+            |store| store.put_with_key(&JsValue::from_str(value), &JsValue::from_str(key)).map(drop),
+            move |result| if let Err(error) = result {
+                notify(SaveEvent::Failed(SaveError { error, ..failed }));
+            },
+        );
+        self.cache.set(key, value)
+    }
+
+    fn remove(&self, key: &str) {
+        self.transact(|store| store.delete(&JsValue::from_str(key)).map(drop), |result| if let Err(err) = result {
+            log::error!("IndexedDB delete failed: {}", err);
+        });
+        self.cache.remove(key);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.cache.keys()
+    }
+
+    // The cache only takes the changes once the transaction has committed, so nothing changes if it aborts.
+    fn apply(&self, changes: Vec<(String, Option<String>)>, on_done: Box<dyn FnOnce(Result<(), BackendError>)>) {
+        let cache = self.cache.clone();
+        let staged = changes.clone();
+        self.transact(
+            move |store| {
+                for (key, value) in &staged {
+                    match value {
+                        Some(value) => store.put_with_key(&JsValue::from_str(value), &JsValue::from_str(key))?,
+                        None => store.delete(&JsValue::from_str(key))?,
+                    };
+                }
+                Ok(())
+            },
+            move |result| {
+                if result.is_ok() {
+                    cache.apply(changes, Box::new(|_| ()));
+                }
+                on_done(result);
+            },
+        );
+    }
+}
+
+thread_local! {
+    static BACKEND: RefCell<Rc<dyn StorageBackend>> = RefCell::new(default_backend());
+}
+
+#[cfg(target_arch = "wasm32")]
+fn default_backend() -> Rc<dyn StorageBackend> {
+    Rc::new(LocalBackend)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_backend() -> Rc<dyn StorageBackend> {
+    Rc::new(MemoryBackend::default())
+}
+
+// The backend the app reads from and writes to.
+pub fn backend() -> Rc<dyn StorageBackend> {
+    BACKEND.with(|backend| backend.borrow().clone())
+}
+
+pub fn set_backend(backend: Rc<dyn StorageBackend>) {
+    BACKEND.with(|current| *current.borrow_mut() = backend);
+}
+
+// Every value Star persists is wrapped in this envelope, so old payloads can be recognised and upgraded.
+#[derive(Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub version: u32,
+    pub data: T,
+}
+
+// Upgrades a payload by exactly one version.
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Unreadable(String),
+    TooNew(u32),
+    Failed { from: u32, reason: String },
+    Invalid(String),
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::Unreadable(reason) => write!(f, "stored value is not valid JSON ({})", reason),
+            MigrationError::TooNew(version) => write!(f, "stored value is version {}, which this build does not understand", version),
+            MigrationError::Failed { from, reason } => write!(f, "migration from version {} failed ({})", from, reason),
+            MigrationError::Invalid(reason) => write!(f, "migrated value does not match the current schema ({})", reason),
+        }
+    }
+}
+
+pub struct Schema<T> {
+    pub key: &'static str,
+    pub label: &'static str,
+    // migrations[n] upgrades a version n payload to version n + 1. Payloads written before
+    // the envelope existed are version 0, so the current version is migrations.len().
+    pub migrations: &'static [Migration],
+    data: PhantomData<fn() -> T>,
+}
+
+// Version 0 -> 1: payloads were stored bare before the envelope was introduced, the data itself is unchanged.
+pub fn wrap_unversioned(value: Value) -> Result<Value, String> {
+    Ok(value)
+}
+
+impl<T> Schema<T> {
+    pub const fn new(key: &'static str, label: &'static str, migrations: &'static [Migration]) -> Self {
+        Schema { key, label, migrations, data: PhantomData }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Schema<T> {
+    pub fn load<B: StorageBackend + ?Sized>(&self, backend: &B) -> Option<T> {
+        self.load_from(backend, self.key)
+    }
+
+    pub fn save<B: StorageBackend + ?Sized>(&self, backend: &B, data: &T) -> Result<(), SaveError> {
+        self.save_to(backend, self.key, data)
+    }
+
+    // Schemas for per-card state store each card's value under its own key.
+    pub fn instance_key(&self, instance: impl std::fmt::Display) -> String {
+        format!("{}.{}", self.key, instance)
+    }
+
+    pub fn load_from<B: StorageBackend + ?Sized>(&self, backend: &B, key: &str) -> Option<T> {
+        let raw = backend.get(key)?;
+        match self.upgrade(&raw) {
+            Ok(data) => Some(data),
+            Err(err) => {
+                // Keep the payload around so it can be recovered once the migration is fixed.
+                let backup_key = key.to_owned() + UNMIGRATED_SUFFIX;
+                log::error!("Could not load \"{}\", raw value kept under \"{}\": {}", key, backup_key, err);
+                if let Err(err) = backend.set(&backup_key, &raw) {
+                    log::error!("{}", err);
+                }
+                notify(SaveEvent::Unreadable(LoadError { key: key.to_owned(), label: self.label, backup_key, reason: err.to_string() }));
+                None
+            }
+        }
+    }
+
+    pub fn save_to<B: StorageBackend + ?Sized>(&self, backend: &B, key: &str, data: &T) -> Result<(), SaveError> {
+        if FROZEN.with(Cell::get) {
+            return Ok(());
+        }
+        let error = |payload, error| SaveError { key: key.to_owned(), label: self.label, payload, error };
+        let result = match serde_json::to_string(&Versioned { version: self.version(), data }) {
+            Ok(raw) => backend.set(key, &raw).map_err(|err| error(Some(raw), err)),
+            Err(err) => Err(error(None, BackendError::Serialize(err.to_string()))),
+        };
+        notify(match &result {
+            Ok(()) => SaveEvent::Saved(key.to_owned()),
+            Err(err) => SaveEvent::Failed(err.clone()),
+        });
+        result
+    }
+
+    pub fn upgrade(&self, raw: &str) -> Result<T, MigrationError> {
+        let value: Value = serde_json::from_str(raw).map_err(|err| MigrationError::Unreadable(err.to_string()))?;
+        let (mut version, mut data) = unwrap(value);
+        if version > self.version() {
+            return Err(MigrationError::TooNew(version));
+        }
+        while version < self.version() {
+            data = self.migrations[version as usize](data).map_err(|reason| MigrationError::Failed { from: version, reason })?;
+            version += 1;
+        }
+        serde_json::from_value(data).map_err(|err| MigrationError::Invalid(err.to_string()))
+    }
+}
+
+fn unwrap(value: Value) -> (u32, Value) {
+    match value {
+        Value::Object(mut map) if map.len() == 2 && map.contains_key("data") && map.get("version").is_some_and(Value::is_u64) => {
+            let version = map["version"].as_u64().unwrap_or_default() as u32;
+            (version, map.remove("data").unwrap_or_default())
+        },
+        value => (0, value),
+    }
+}
+
+const UNMIGRATED_SUFFIX: &str = ".unmigrated";
+
+// Type-erased view of a schema, so backups can cover every stored value.
+pub trait Stored {
+    fn key(&self) -> &'static str;
+    fn label(&self) -> &'static str;
+    fn check(&self, raw: &str) -> Result<(), MigrationError>;
+
+    // True for the schema's own key and its per-card instance keys.
+    fn owns(&self, key: &str) -> bool {
+        match key.strip_prefix(self.key()) {
+            Some(rest) => rest.is_empty() || (rest.starts_with('.') && !rest.ends_with(UNMIGRATED_SUFFIX)),
+            None => false,
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Stored for Schema<T> {
+    fn key(&self) -> &'static str {
+        self.key
+    }
+
+    fn label(&self) -> &'static str {
+        self.label
+    }
+
+    fn check(&self, raw: &str) -> Result<(), MigrationError> {
+        self.upgrade(raw).map(|_| ())
+    }
+}
+
+// Every schema the app persists. A stored type has to be listed here to be part of backups.
+pub const STORED: &[&dyn Stored] = &[
+    &state::CARDS,
+    &state::PRIORITY_CARDS,
+    &state::HISTORY,
+    &state::DAILY_TASKS,
+    &components::CS_CYCLE,
+    &timers::KITCHEN_TIMERS,
+    &alarm::ALARM_SETTINGS,
+    &history::CS_HISTORY,
+    &checklist::CS_CHECKLIST,
+    &rotation::BREW_ROTATION,
+    &roasts::ROAST_CATALOG,
+    &urns::URNS,
+    &products::DAYDOT_PRODUCTS,
+];
+
+fn stored_for(key: &str) -> Option<&'static dyn Stored> {
+    STORED.iter().copied().find(|stored| stored.owns(key))
+}
+
+thread_local! {
+    static FROZEN: Cell<bool> = const { Cell::new(false) };
+}
+
+// Stops schemas from writing while an import is stored and the page reloads, so components
+// saving their state in the meantime don't overwrite the imported values.
+pub fn freeze() {
+    FROZEN.with(|frozen| frozen.set(true));
+}
+
+// Lets schemas write again after an import failed.
+pub fn thaw() {
+    FROZEN.with(|frozen| frozen.set(false));
+}
+
+// Backups
+const BUNDLE_FORMAT: &str = "star-backup";
+const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct Bundle {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub entries: BTreeMap<String, Value>,
+}
+
+#[derive(Debug)]
+pub enum BundleError {
+    Unreadable(String),
+    NotABackup,
+    TooNew(u32),
+    UnknownEntry(String),
+    Entry(String, MigrationError),
+}
+
+impl std::fmt::Display for BundleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BundleError::Unreadable(reason) => write!(f, "the file is not valid JSON ({})", reason),
+            BundleError::NotABackup => write!(f, "the file is not a Star backup"),
+            BundleError::TooNew(version) => write!(f, "the backup is version {}, which this build does not understand", version),
+            BundleError::UnknownEntry(key) => write!(f, "the backup contains unknown data \"{}\"", key),
+            BundleError::Entry(key, err) => write!(f, "\"{}\" is invalid: {}", key, err),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ImportMode {
+    // Only overwrites what is in the backup.
+    Merge,
+    // Also removes stored data the backup doesn't have.
+    Replace,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Change {
+    Added,
+    Changed,
+    Unchanged,
+    // Stored on this device but missing from the backup.
+    LocalOnly,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct EntryDiff {
+    pub key: String,
+    pub label: &'static str,
+    pub change: Change,
+}
+
+impl Bundle {
+    pub fn export<B: StorageBackend + ?Sized>(backend: &B) -> Bundle {
+        let entries = backend.keys().into_iter()
+            .filter(|key| stored_for(key).is_some())
+            .filter_map(|key| {
+                let raw = backend.get(&key)?;
+                let value = serde_json::from_str(&raw).ok()?;
+                Some((key, value))
+            })
+            .collect();
+        Bundle {
+            format: BUNDLE_FORMAT.to_owned(),
+            version: BUNDLE_VERSION,
+            exported_at: clock::now(),
+            entries,
+        }
+    }
+
+    // Parses a backup and checks that every entry loads with the current schemas.
+    pub fn parse(text: &str) -> Result<Bundle, BundleError> {
+        let bundle: Bundle = serde_json::from_str(text).map_err(|err| BundleError::Unreadable(err.to_string()))?;
+        if bundle.format != BUNDLE_FORMAT {
+            return Err(BundleError::NotABackup);
+        }
+        if bundle.version > BUNDLE_VERSION {
+            return Err(BundleError::TooNew(bundle.version));
+        }
+        for (key, value) in &bundle.entries {
+            let stored = stored_for(key).ok_or_else(|| BundleError::UnknownEntry(key.clone()))?;
+            stored.check(&value.to_string()).map_err(|err| BundleError::Entry(key.clone(), err))?;
+        }
+        Ok(bundle)
+    }
+
+    pub fn diff<B: StorageBackend + ?Sized>(&self, backend: &B) -> Vec<EntryDiff> {
+        let mut diff: Vec<EntryDiff> = self.entries.iter().map(|(key, value)| {
+            let local: Option<Value> = backend.get(key).and_then(|raw| serde_json::from_str(&raw).ok());
+            EntryDiff {
+                key: key.clone(),
+                label: stored_for(key).map_or("", |stored| stored.label()),
+                change: match local {
+                    None => Change::Added,
+                    Some(local) if &local == value => Change::Unchanged,
+                    Some(_) => Change::Changed,
+                },
+            }
+        }).collect();
+        for key in backend.keys() {
+            if let Some(stored) = stored_for(&key) {
+                if !self.entries.contains_key(&key) {
+                    diff.push(EntryDiff { key, label: stored.label(), change: Change::LocalOnly });
+                }
+            }
+        }
+        diff
+    }
+
+    // Stores the whole backup or, if anything fails, none of it. on_done is called once the backend is done.
+    pub fn import<B: StorageBackend + ?Sized>(&self, backend: &B, mode: ImportMode, on_done: impl FnOnce(Result<(), BackendError>) + 'static) {
+        let mut changes: Vec<(String, Option<String>)> = self.entries.iter().map(|(key, value)| (key.clone(), Some(value.to_string()))).collect();
+        if mode == ImportMode::Replace {
+            changes.extend(backend.keys().into_iter()
+                .filter(|key| stored_for(key).is_some() && !self.entries.contains_key(key))
+                .map(|key| (key, None)));
+        }
+        backend.apply(changes, Box::new(on_done));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{checklist::CsChecklist, state::StarData, urns::Urns};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Counter {
+        count: u32,
+        label: String,
+    }
+
+    // Version 0 -> 1: count was called total.
+    fn rename_total(mut data: Value) -> Result<Value, String> {
+        let total = data.get_mut("total").map(Value::take).ok_or("no total")?;
+        data["count"] = total;
+        Ok(data)
+    }
+
+    // Version 1 -> 2: label was added.
+    fn add_label(mut data: Value) -> Result<Value, String> {
+        data["label"] = json!("counter");
+        Ok(data)
+    }
+
+    const COUNTER: Schema<Counter> = Schema::new("counter", "Counter", &[rename_total, add_label]);
+
+    #[test]
+    fn bare_payloads_run_every_migration() {
+        assert_eq!(COUNTER.upgrade(r#"{"total":3}"#).unwrap(), Counter { count: 3, label: "counter".to_string() });
+    }
+
+    #[test]
+    fn versioned_payloads_run_the_migrations_after_their_version() {
+        let raw = r#"{"version":1,"data":{"count":3}}"#;
+        assert_eq!(COUNTER.upgrade(raw).unwrap(), Counter { count: 3, label: "counter".to_string() });
+    }
+
+    #[test]
+    fn newer_payloads_are_refused() {
+        let raw = r#"{"version":3,"data":{"count":3,"label":"counter"}}"#;
+        assert!(matches!(COUNTER.upgrade(raw), Err(MigrationError::TooNew(3))));
+        assert!(matches!(COUNTER.upgrade("{"), Err(MigrationError::Unreadable(_))));
+        assert!(matches!(COUNTER.upgrade(r#"{"version":2,"data":{"count":"three"}}"#), Err(MigrationError::Invalid(_))));
+    }
+
+    #[test]
+    fn failed_migrations_keep_the_raw_value() {
+        let backend = MemoryBackend::default();
+        backend.set("counter", r#"{"count":3}"#).unwrap();
+        assert!(COUNTER.load(&backend).is_none());
+        assert!(matches!(COUNTER.upgrade(r#"{"count":3}"#), Err(MigrationError::Failed { from: 0, .. })));
+        assert_eq!(backend.get("counter.unmigrated").as_deref(), Some(r#"{"count":3}"#));
+    }
+
+    #[test]
+    fn saved_values_load_back() {
+        let backend = MemoryBackend::default();
+        let counter = Counter { count: 5, label: "cups".to_string() };
+        COUNTER.save(&backend, &counter).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&backend.get("counter").unwrap()).unwrap(), json!({ "version": 2, "data": { "count": 5, "label": "cups" } }));
+        assert_eq!(COUNTER.load(&backend), Some(counter));
+    }
+
+    #[test]
+    fn failed_loads_are_reported_once_there_is_a_listener() {
+        let backend = MemoryBackend::default();
+        backend.set("counter", r#"{"count":3}"#).unwrap();
+        assert!(COUNTER.load(&backend).is_none());
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let seen = events.clone();
+        set_save_listener(Some(Callback::from(move |event| seen.borrow_mut().push(event))));
+        assert!(matches!(&events.borrow()[..], [SaveEvent::Unreadable(LoadError { key, backup_key, .. })] if key == "counter" && backup_key == "counter.unmigrated"));
+
+        backend.set("counter", "{").unwrap();
+        assert!(COUNTER.load(&backend).is_none());
+        assert_eq!(events.borrow().len(), 2);
+        set_save_listener(None);
+    }
+
+
+    #[test]
+    fn bundles_round_trip() {
+        let backend = MemoryBackend::default();
+        CsChecklist::default().save(&backend).unwrap();
+        Urns::default().save(&backend).unwrap();
+        StarData::load(&backend).save(&backend).unwrap();
+        backend.set("not_star_data", "1").unwrap();
+
+        let text = serde_json::to_string(&Bundle::export(&backend)).unwrap();
+        let bundle = Bundle::parse(&text).unwrap();
+        assert_eq!(bundle.entries.keys().collect::<Vec<_>>(), vec!["card_history", "cards", "cs_checklist", "priority_cards", "urns"]);
+
+        let restored = MemoryBackend::default();
+        assert!(bundle.diff(&restored).iter().all(|entry| entry.change == Change::Added));
+        bundle.import(&restored, ImportMode::Merge, Result::unwrap);
+        assert!(bundle.diff(&restored).iter().all(|entry| entry.change == Change::Unchanged));
+        assert!(CsChecklist::load(&restored) == CsChecklist::default());
+        assert!(Urns::load(&restored) == Urns::default());
+    }
+
+    #[test]
+    fn replacing_removes_what_the_bundle_lacks() {
+        let backend = MemoryBackend::default();
+        CsChecklist::default().save(&backend).unwrap();
+        let bundle = Bundle::export(&backend);
+
+        Urns::default().save(&backend).unwrap();
+        let diff = bundle.diff(&backend);
+        assert!(diff.iter().any(|entry| entry.key == "urns" && entry.change == Change::LocalOnly));
+
+        bundle.import(&backend, ImportMode::Merge, Result::unwrap);
+        assert!(backend.get("urns").is_some());
+        bundle.import(&backend, ImportMode::Replace, Result::unwrap);
+        assert!(backend.get("urns").is_none());
+        assert!(backend.get("cs_checklist").is_some());
+    }
+
+    #[test]
+    fn bundles_with_invalid_entries_are_refused() {
+        let bundle = |entries: Value| json!({ "format": BUNDLE_FORMAT, "version": 1, "exported_at": "2024-03-05T09:00:00Z", "entries": entries }).to_string();
+        assert!(matches!(Bundle::parse(&bundle(json!({ "mystery": 1 }))), Err(BundleError::UnknownEntry(_))));
+        assert!(matches!(Bundle::parse(&bundle(json!({ "urns": { "version": 0, "data": 1 } }))), Err(BundleError::Entry(..))));
+        assert!(matches!(Bundle::parse(r#"{"format":"other","version":1,"exported_at":"2024-03-05T09:00:00Z","entries":{}}"#), Err(BundleError::NotABackup)));
+    }
+
+    // Refuses to store the urns, like a full quota would.
+    struct FullBackend(MemoryBackend);
+
+    impl StorageBackend for FullBackend {
+        fn get(&self, key: &str) -> Option<String> {
+            self.0.get(key)
+        }
+
+        fn set(&self, key: &str, value: &str) -> Result<(), BackendError> {
+            match key {
+                "urns" => Err(BackendError::QuotaExceeded),
+                _ => self.0.set(key, value),
+            }
+        }
+
+        fn remove(&self, key: &str) {
+            self.0.remove(key)
+        }
+
+        fn keys(&self) -> Vec<String> {
+            self.0.keys()
+        }
+    }
+
+    #[test]
+    fn failed_imports_keep_the_old_data() {
+        let source = MemoryBackend::default();
+        CsChecklist::default().save(&source).unwrap();
+        Urns::default().save(&source).unwrap();
+        let bundle = Bundle::export(&source);
+
+        let backend = FullBackend(MemoryBackend::default());
+        backend.set("cs_checklist", "old").unwrap();
+        backend.set("cards", "old").unwrap();
+        let result = Rc::new(RefCell::new(None));
+        let done = result.clone();
+        bundle.import(&backend, ImportMode::Replace, move |outcome| *done.borrow_mut() = Some(outcome));
+        assert_eq!(*result.borrow(), Some(Err(BackendError::QuotaExceeded)));
+        assert_eq!(backend.get("cs_checklist").as_deref(), Some("old"));
+        assert_eq!(backend.get("cards").as_deref(), Some("old"));
+    }
+}
@@ -0,0 +1,309 @@
+use std::rc::Rc;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use web_sys::{HtmlInputElement, KeyboardEvent};
+use yew::prelude::*;
+
+use crate::{cards::CardKind, clock, notifications, alarm::{use_alarm, AlarmBanner, AlarmKind, AlarmSettingsEditor}, components::{CardData, CardId, Timer}, state::{TimerData, TimerAction, TimerInterval, TimerEvent}, storage::{self, StorableData, StorageBackend, Schema, SaveError}};
+
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum KitchenTimerState {
+    Ready,
+    Running(DateTime<Utc>),
+    // Seconds left when the timer was paused.
+    Paused(i32),
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct KitchenTimer {
+    pub id: u64,
+    pub name: String,
+    pub duration: i32, // Seconds
+    pub state: KitchenTimerState,
+}
+
+impl KitchenTimer {
+    pub fn time_left(&self) -> i32 {
+        match self.state {
+            KitchenTimerState::Ready => self.duration,
+            KitchenTimerState::Running(deadline) => TimerData::seconds_until(deadline),
+            KitchenTimerState::Paused(seconds) => seconds,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct KitchenTimers {
+    pub timers: Vec<KitchenTimer>,
+    #[serde(skip)]
+    pub card: Option<CardId>,
+}
+
+pub const KITCHEN_TIMERS: Schema<KitchenTimers> = Schema::new("kitchen_timers", "Kitchen timers", &[]);
+
+impl StorableData for KitchenTimers {
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self {
+        KITCHEN_TIMERS.load(backend).unwrap_or_default()
+    }
+
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        match self.card {
+            Some(card) => KITCHEN_TIMERS.save_to(backend, &KITCHEN_TIMERS.instance_key(card), self),
+            None => KITCHEN_TIMERS.save(backend, self),
+        }
+    }
+}
+
+impl KitchenTimers {
+    pub fn load_for_card<B: StorageBackend + ?Sized>(backend: &B, card: CardId) -> Self {
+        let mut data = KITCHEN_TIMERS.load_from(backend, &KITCHEN_TIMERS.instance_key(card)).unwrap_or_default();
+        data.card = Some(card);
+        data
+    }
+
+}
+
+pub enum KitchenTimersAction {
+    Add(KitchenTimer),
+    Change(KitchenTimer),
+    Remove(u64),
+}
+
+// Rows keep the callbacks they were first rendered with, going through the reducer means they always change the latest list.
+impl Reducible for KitchenTimers {
+    type Action = KitchenTimersAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let mut data = (*self).clone();
+        match action {
+            KitchenTimersAction::Add(timer) => data.timers.push(timer),
+            KitchenTimersAction::Change(timer) => match data.timers.iter_mut().find(|existing| existing.id == timer.id) {
+                Some(existing) if *existing != timer => *existing = timer,
+                _ => return self,
+            },
+            KitchenTimersAction::Remove(id) => data.timers.retain(|timer| timer.id != id),
+        }
+        // Running timers are stored as deadlines, so saving on every change is enough to survive a reload.
+        data.persist();
+        data.into()
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct KitchenTimerListData {
+    pub card: CardId,
+}
+
+#[function_component]
+pub fn KitchenTimerList(data: &KitchenTimerListData) -> Html {
+    let card = data.card;
+    let timers = use_reducer(|| KitchenTimers::load_for_card(&*storage::backend(), card));
+    let name_input_ref = use_node_ref();
+    let duration_input_ref = use_node_ref();
+    let duration_error = use_state(|| None::<String>);
+    // Only used to edit the settings, each timer row rings its own alarm.
+    let alarm = use_alarm(AlarmKind::KitchenTimer);
+    let show_alarm_settings = use_state(|| false);
+    let toggle_alarm_settings = {
+        let show_alarm_settings = show_alarm_settings.clone();
+        Callback::from(move |_| show_alarm_settings.set(!*show_alarm_settings))
+    };
+
+    let add_timer = {
+        let timers = timers.clone();
+        let name_input_ref = name_input_ref.clone();
+        let duration_input_ref = duration_input_ref.clone();
+        let duration_error = duration_error.clone();
+        Callback::from(move |_| {
+            let name_input = name_input_ref.cast::<HtmlInputElement>().expect("Timer name input not initialized correctly.");
+            let duration_input = duration_input_ref.cast::<HtmlInputElement>().expect("Timer duration input not initialized correctly.");
+            let duration = match TimerData::seconds_from_str(&duration_input.value()) {
+                Ok(duration) if duration > 0 => duration,
+                Ok(_) => {
+                    duration_error.set(Some("A timer needs to be longer than 0 seconds.".to_string()));
+                    return;
+                },
+                Err(err) => {
+                    duration_error.set(Some(err.to_string()));
+                    return;
+                }
+            };
+            duration_error.set(None);
+            let name = name_input.value().trim().to_string();
+            timers.dispatch(KitchenTimersAction::Add(KitchenTimer {
+                id: clock::unique_id(),
+                name: if name.is_empty() { format!("Timer {}", timers.timers.len() + 1) } else { name },
+                duration,
+                state: KitchenTimerState::Ready,
+            }));
+            name_input.set_value("");
+            duration_input.set_value("");
+        })
+    };
+
+    let add_on_enter = {
+        let add_timer = add_timer.clone();
+        Callback::from(move |e: KeyboardEvent| {
+            if e.key() == "Enter" {
+                add_timer.emit(());
+            }
+        })
+    };
+
+    let change_timer = {
+        let timers = timers.clone();
+        Callback::from(move |timer: KitchenTimer| timers.dispatch(KitchenTimersAction::Change(timer)))
+    };
+
+    let remove_timer = {
+        let timers = timers.clone();
+        Callback::from(move |id: u64| timers.dispatch(KitchenTimersAction::Remove(id)))
+    };
+
+    html! {
+        <>
+            {
+                for timers.timers.iter().map(|timer| html! {
+                    <KitchenTimerRow key={ timer.id.to_string() } timer={ timer.clone() } card={ card } on_change={ change_timer.clone() } on_remove={ remove_timer.clone() } />
+                })
+            }
+            if timers.timers.is_empty() {
+                <p>{ "No timers yet. Give one a name and a time like 4:00 to add it." }</p>
+            }
+            <hr/>
+            <input ref={ name_input_ref } onkeydown={ add_on_enter.clone() } class="text_input" size="1" type="text" placeholder="Name, e.g. Oven pull" />
+            <input ref={ duration_input_ref } onkeydown={ add_on_enter } class="text_input" size="1" type="text" placeholder="Time, e.g. 4:00 or 1h 30m" />
+            if let Some(error) = &*duration_error {
+                <p class="input_error">{ error }</p>
+            }
+            <button class="button" onclick={ add_timer.reform(|_| ()) }><span class="material-symbols-outlined icon">{ "add" }</span>{ " Add Timer" }</button>
+            <a class="settings_toggle clickable" onclick={toggle_alarm_settings}>
+                <span class="material-symbols-outlined">{ "notifications" }</span>{ " Alarm settings" }
+            </a>
+            if *show_alarm_settings {
+                <AlarmSettingsEditor alarm={ alarm.clone() } />
+            }
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct KitchenTimerRowData {
+    pub timer: KitchenTimer,
+    pub card: CardId,
+    pub on_change: Callback<KitchenTimer>,
+    pub on_remove: Callback<u64>,
+}
+
+#[function_component]
+fn KitchenTimerRow(data: &KitchenTimerRowData) -> Html {
+    let timer = &data.timer;
+    let time_left = use_state(|| timer.time_left());
+    let alarm = use_alarm(AlarmKind::KitchenTimer);
+    let timer_state = {
+        let time_left = time_left.clone();
+        let alarm = alarm.clone();
+        let card = data.card;
+        // Tagged by timer, so timers in the same card that run out together each keep their notification.
+        let tag = format!("star-timer-{}", timer.id);
+        let name = timer.name.clone();
+        use_reducer(move || {
+            TimerData {
+                deadline: None,
+                time_left,
+                paused_at: None,
+                paused_seconds: 0,
+                running: false,
+                callback: Callback::from(move |event| match event {
+                    TimerEvent::Expired => {
+                        alarm.ring();
+                        notifications::post("Timer done", &format!("{} is done.", name), &tag, Some(card));
+                    },
+                    TimerEvent::Stopped => alarm.acknowledge(),
+                    _ => (),
+                }),
+                warning_seconds: 60,
+                interval: TimerInterval::default(),
+            }
+        })
+    };
+
+    // The stored state drives the timer, so a reload picks up exactly where it left off.
+    {
+        let timer_state = timer_state.clone();
+        use_effect_with_deps(move |timer: &KitchenTimer| {
+            match timer.state {
+                KitchenTimerState::Running(deadline) => timer_state.dispatch(TimerAction::Continue(deadline)),
+                _ => {
+                    timer_state.dispatch(TimerAction::Stop);
+                    timer_state.time_left.set(timer.time_left());
+                }
+            }
+            // Dispatching reduces the latest state, so this clears the interval even after the row is removed.
+            move || timer_state.dispatch(TimerAction::Stop)
+        }, timer.clone());
+    }
+
+    // States are worked out on click, a deadline taken at render time would already be out of date.
+    let with_state = |state: fn(&KitchenTimer) -> KitchenTimerState| {
+        let on_change = data.on_change.clone();
+        let timer = timer.clone();
+        Callback::from(move |_: MouseEvent| on_change.emit(KitchenTimer { state: state(&timer), ..timer.clone() }))
+    };
+    let start = with_state(|timer| KitchenTimerState::Running(TimerData::deadline_in(timer.time_left())));
+    let pause = with_state(|timer| KitchenTimerState::Paused(timer.time_left()));
+    let reset = with_state(|_| KitchenTimerState::Ready);
+    let remove = {
+        let on_remove = data.on_remove.clone();
+        let id = timer.id;
+        Callback::from(move |_| on_remove.emit(id))
+    };
+
+    html! {
+        <div class={ classes!("kitchen_timer", (*time_left < 0).then_some("kitchen_timer_expired")) }>
+            <p class="kitchen_timer_name"><b>{ timer.name.clone() }</b></p>
+            <Timer time_left={ time_left.clone() } />
+            if let KitchenTimerState::Running(_) = timer.state {
+                <button class="button" onclick={ pause }><span class="material-symbols-outlined icon">{ "pause" }</span>{ " Pause" }</button>
+            } else {
+                <button class="button" onclick={ start }><span class="material-symbols-outlined icon">{ "play_arrow" }</span>{ if timer.state == KitchenTimerState::Ready { " Start" } else { " Resume" } }</button>
+            }
+            { " " }
+            <button class="button outlined" onclick={ reset }>{ "Reset" }</button>
+            { " " }
+            <button class="button outlined" onclick={ remove }>{ "Remove" }</button>
+            <AlarmBanner alarm={ alarm.clone() } message={ format!("{} is done", timer.name) } />
+        </div>
+    }
+}
+
+pub struct KitchenTimersCard;
+
+impl CardKind for KitchenTimersCard {
+    fn key(&self) -> &'static str {
+        "KitchenTimers"
+    }
+
+    fn name(&self) -> &'static str {
+        "Timers"
+    }
+
+    fn icon(&self) -> &'static str {
+        "timer"
+    }
+
+    fn launcher_label(&self) -> Option<&'static str> {
+        Some("Run some kitchen timers")
+    }
+
+    fn content(&self, card: &CardData) -> Html {
+        html! {
+            <KitchenTimerList card={ card.id } />
+        }
+    }
+
+    fn persistence_key(&self) -> Option<&'static str> {
+        Some(KITCHEN_TIMERS.key)
+    }
+}
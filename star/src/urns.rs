@@ -0,0 +1,298 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use gloo::timers::callback::Interval;
+use serde::{Serialize, Deserialize};
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::{clock, components::CoffeesToBrew, notifications, roasts::{RoastCatalog, RoastId}, state::TimerData, storage::{self, Schema, SaveError, StorableData, StorageBackend}};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Urn {
+    pub id: u64,
+    pub name: String,
+    // What's in it and since when, both None while it's empty.
+    pub roast: Option<RoastId>,
+    pub brewed_at: Option<DateTime<Utc>>,
+    // Set once staff have been told to dump it, stored so other cards and reloads don't post it again.
+    #[serde(default)]
+    pub dump_posted: bool,
+}
+
+impl Urn {
+    // When the coffee in it is past its hold time.
+    pub fn dump_at(&self, catalog: &RoastCatalog) -> Option<DateTime<Utc>> {
+        let hold_minutes = self.roast.and_then(|roast| catalog.get(roast)).map_or(DEFAULT_HOLD_MINUTES, |roast| roast.hold_minutes);
+        self.brewed_at.map(|brewed_at| brewed_at + Duration::minutes(hold_minutes.into()))
+    }
+
+    fn fill(&mut self, roast: RoastId, at: DateTime<Utc>) {
+        self.roast = Some(roast);
+        self.brewed_at = Some(at);
+        self.dump_posted = false;
+    }
+
+    fn dump(&mut self) {
+        self.roast = None;
+        self.brewed_at = None;
+        self.dump_posted = false;
+    }
+}
+
+// Used for urns holding a roast that has since been taken out of the catalog.
+const DEFAULT_HOLD_MINUTES: u32 = 30;
+// How long before its hold time is up an urn starts to warn.
+const WARNING_SECONDS: i32 = 5 * 60;
+
+// The store's urns, shared by every CS cycle card.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Urns {
+    pub urns: Vec<Urn>,
+}
+
+impl Default for Urns {
+    fn default() -> Self {
+        Urns {
+            urns: (1..=3).map(|id| Urn { id, name: format!("Urn {}", id), roast: None, brewed_at: None, dump_posted: false }).collect(),
+        }
+    }
+}
+
+pub const URNS: Schema<Urns> = Schema::new("urns", "Urns", &[]);
+
+impl StorableData for Urns {
+    fn load<B: StorageBackend + ?Sized>(backend: &B) -> Self {
+        URNS.load(backend).unwrap_or_default()
+    }
+
+    fn save<B: StorageBackend + ?Sized>(&self, backend: &B) -> Result<(), SaveError> {
+        URNS.save(backend, self)
+    }
+}
+
+impl Urns {
+    // Pours a fresh brew into the urns. Each roast goes into the urn already holding it, then an empty
+    // one, then whichever has been sitting the longest.
+    pub fn fill(&mut self, brew: &CoffeesToBrew, at: DateTime<Utc>) {
+        let mut filled: Vec<u64> = Vec::new();
+        for roast in &brew.0 {
+            let unfilled = || self.urns.iter().filter(|urn| !filled.contains(&urn.id));
+            let target = unfilled().find(|urn| urn.roast == Some(*roast))
+                .or_else(|| unfilled().find(|urn| urn.brewed_at.is_none()))
+                .or_else(|| unfilled().min_by_key(|urn| urn.brewed_at))
+                .map(|urn| urn.id);
+            if let Some(urn) = target.and_then(|id| self.urns.iter_mut().find(|urn| urn.id == id)) {
+                urn.fill(*roast, at);
+                filled.push(urn.id);
+            }
+        }
+    }
+
+    // Urns that have gone past their hold time since the last call, marked so they're only reported once.
+    pub fn newly_past_hold_time(&mut self, catalog: &RoastCatalog, now: DateTime<Utc>) -> Vec<Urn> {
+        let mut past = Vec::new();
+        for urn in self.urns.iter_mut().filter(|urn| !urn.dump_posted) {
+            if urn.dump_at(catalog).is_some_and(|dump_at| dump_at <= now) {
+                urn.dump_posted = true;
+                past.push(urn.clone());
+            }
+        }
+        past
+    }
+
+    fn update(&self, id: u64, change: impl FnOnce(&mut Urn)) -> Self {
+        let mut urns = self.clone();
+        if let Some(urn) = urns.urns.iter_mut().find(|urn| urn.id == id) {
+            change(urn);
+        }
+        urns
+    }
+}
+
+#[function_component]
+pub fn UrnTracker() -> Html {
+    let urns = use_state_eq(|| Urns::load(&*storage::backend()));
+    let catalog = use_state_eq(|| RoastCatalog::load(&*storage::backend()));
+    let show_editor = use_state(|| false);
+    let update = use_force_update();
+
+    // Ticks the countdowns. Other cards and the CS cycle fill urns too, so they're read back each tick.
+    {
+        let urns = urns.clone();
+        let catalog = catalog.clone();
+        use_effect_with_deps(move |_| {
+            let interval = Interval::new(1000, move || {
+                let mut latest = Urns::load(&*storage::backend());
+                let latest_catalog = RoastCatalog::load(&*storage::backend());
+                let past = latest.newly_past_hold_time(&latest_catalog, clock::now());
+                for urn in &past {
+                    let roast = urn.roast.map_or_else(|| "coffee".to_string(), |roast| latest_catalog.name(roast));
+                    notifications::post("Coffee past its hold time", &format!("Dump the {} in {}.", roast, urn.name), &format!("star-urn-{}", urn.id), None);
+                }
+                // Saved straight away, every other tracker reads it back on its next tick.
+                if !past.is_empty() {
+                    latest.persist();
+                }
+                urns.set(latest);
+                catalog.set(latest_catalog);
+                update.force_update();
+            });
+            move || drop(interval)
+        }, ());
+    }
+
+    let set_urns = {
+        let urns = urns.clone();
+        Callback::from(move |data: Urns| {
+            data.persist();
+            urns.set(data);
+        })
+    };
+    let brew = |id: u64| {
+        let urns = urns.clone();
+        let set_urns = set_urns.clone();
+        Callback::from(move |e: Event| {
+            if let Ok(roast) = e.target_unchecked_into::<HtmlSelectElement>().value().parse::<u64>() {
+                set_urns.emit(urns.update(id, |urn| urn.fill(RoastId(roast), clock::now())));
+            }
+        })
+    };
+    let dump = |id: u64| {
+        let urns = urns.clone();
+        let set_urns = set_urns.clone();
+        Callback::from(move |_| set_urns.emit(urns.update(id, Urn::dump)))
+    };
+    let rename = |id: u64| {
+        let urns = urns.clone();
+        let set_urns = set_urns.clone();
+        Callback::from(move |e: Event| {
+            let name = e.target_unchecked_into::<HtmlInputElement>().value().trim().to_string();
+            if !name.is_empty() {
+                set_urns.emit(urns.update(id, |urn| urn.name = name));
+            }
+        })
+    };
+    let remove = |id: u64| {
+        let urns = urns.clone();
+        let set_urns = set_urns.clone();
+        Callback::from(move |_| {
+            let mut data = (*urns).clone();
+            data.urns.retain(|urn| urn.id != id);
+            set_urns.emit(data);
+        })
+    };
+    let add_urn = {
+        let urns = urns.clone();
+        let set_urns = set_urns.clone();
+        Callback::from(move |_| {
+            let mut data = (*urns).clone();
+            data.urns.push(Urn {
+                id: clock::unique_id(),
+                name: format!("Urn {}", data.urns.len() + 1),
+                roast: None,
+                brewed_at: None,
+                dump_posted: false,
+            });
+            set_urns.emit(data);
+        })
+    };
+    let toggle_editor = {
+        let show_editor = show_editor.clone();
+        Callback::from(move |_| show_editor.set(!*show_editor))
+    };
+
+    html! {
+        <div class="urn_tracker">
+            <p><b>{ "Urns" }</b></p>
+            {
+                for urns.urns.iter().map(|urn| {
+                    let seconds_left = urn.dump_at(&catalog).map(TimerData::seconds_until);
+                    html! {
+                        <div key={ urn.id.to_string() } class={ classes!(
+                            "urn",
+                            seconds_left.is_some_and(|seconds| seconds <= WARNING_SECONDS).then_some("urn_warning"),
+                            seconds_left.is_some_and(|seconds| seconds <= 0).then_some("urn_expired"),
+                        ) }>
+                            if *show_editor {
+                                <input class="text_input" type="text" value={ urn.name.clone() } onchange={ rename(urn.id) } />
+                                <button class="button outlined" title="Remove" onclick={ remove(urn.id) }>
+                                    <span class="material-symbols-outlined">{ "delete" }</span>
+                                </button>
+                            } else {
+                                <span class="urn_name">{ urn.name.clone() }</span>
+                                if let (Some(roast), Some(brewed_at), Some(seconds_left)) = (urn.roast, urn.brewed_at, seconds_left) {
+                                    { catalog.tag(roast) }
+                                    <small>{ format!("Brewed {}", brewed_at.with_timezone(&Local).format("%-I:%M %p")) }</small>
+                                    <span class="urn_countdown">{
+                                        if seconds_left <= 0 { "Dump now".to_string() } else { TimerData::format_time_left(seconds_left) }
+                                    }</span>
+                                    <button class="button outlined" onclick={ dump(urn.id) }>{ "Dump" }</button>
+                                } else {
+                                    <span class="urn_empty">{ "Empty" }</span>
+                                    <select onchange={ brew(urn.id) }>
+                                        <option value="" selected=true>{ "Brewed..." }</option>
+                                        {
+                                            for catalog.roasts.iter().filter(|roast| roast.active).map(|roast| html! {
+                                                <option value={ roast.id.0.to_string() }>{ roast.name.clone() }</option>
+                                            })
+                                        }
+                                    </select>
+                                }
+                            }
+                        </div>
+                    }
+                })
+            }
+            if *show_editor {
+                <button class="button outlined" onclick={ add_urn }><span class="material-symbols-outlined">{ "add" }</span>{ " Add Urn" }</button>
+                { " " }
+            }
+            <a class="settings_toggle clickable" onclick={ toggle_editor }>
+                <span class="material-symbols-outlined">{ if *show_editor { "done" } else { "edit" } }</span>{ if *show_editor { " Done" } else { " Edit urns" } }
+            </a>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roasts::{BLONDE, DARK, PIKE};
+
+    #[test]
+    fn brews_go_to_the_urn_holding_the_roast_then_an_empty_one_then_the_oldest() {
+        let clock = clock::fake_local(2024, 3, 5, 9, 0);
+        let mut urns = Urns::default();
+        urns.fill(&CoffeesToBrew(vec![PIKE, BLONDE]), clock::now());
+        clock.advance(Duration::minutes(10));
+        urns.fill(&CoffeesToBrew(vec![BLONDE, DARK]), clock::now());
+        let contents = |urns: &Urns| urns.urns.iter().map(|urn| urn.roast).collect::<Vec<_>>();
+        assert_eq!(contents(&urns), vec![Some(PIKE), Some(BLONDE), Some(DARK)]);
+        assert_eq!(urns.urns[1].brewed_at, Some(clock::now()));
+
+        clock.advance(Duration::minutes(10));
+        urns.fill(&CoffeesToBrew(vec![PIKE, BLONDE, DARK, PIKE]), clock::now());
+        assert_eq!(contents(&urns), vec![Some(PIKE), Some(BLONDE), Some(DARK)]);
+        assert!(urns.urns.iter().all(|urn| urn.brewed_at == Some(clock::now())));
+    }
+
+    #[test]
+    fn urns_are_reported_once_when_past_their_hold_time() {
+        let clock = clock::fake_local(2024, 3, 5, 9, 0);
+        let catalog = RoastCatalog::default();
+        let mut urns = Urns::default();
+        urns.fill(&CoffeesToBrew(vec![PIKE]), clock::now());
+        assert_eq!(urns.urns[0].dump_at(&catalog), Some(clock::now() + Duration::minutes(DEFAULT_HOLD_MINUTES.into())));
+
+        clock.advance(Duration::minutes(29));
+        assert!(urns.newly_past_hold_time(&catalog, clock::now()).is_empty());
+        clock.advance(Duration::minutes(1));
+        assert_eq!(urns.newly_past_hold_time(&catalog, clock::now()).iter().map(|urn| urn.id).collect::<Vec<_>>(), vec![urns.urns[0].id]);
+        assert!(urns.newly_past_hold_time(&catalog, clock::now()).is_empty());
+
+        // A fresh brew starts the hold time over.
+        urns.fill(&CoffeesToBrew(vec![PIKE]), clock::now());
+        assert!(!urns.urns[0].dump_posted);
+        clock.advance(Duration::minutes(30));
+        assert_eq!(urns.newly_past_hold_time(&catalog, clock::now()).len(), 1);
+    }
+}